@@ -0,0 +1,32 @@
+//! handling of labeled sample directories, shared by the captcha tools.
+
+use std::path::{Path, PathBuf};
+use ustc_cas::captcha::LumaImage;
+use ustc_cas::CorpusRecorder;
+
+/// The files in `dir`, sorted by name.
+pub fn files(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut files: Vec<_> = std::fs::read_dir(dir)
+        .map_err(|e| format!("can not read {}: {e}", dir.display()))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_file())
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Read a sample, named after the code it shows, optionally followed by `_` and anything
+/// else, such as `1234.jpg` or `1234_20230401.jpg`. The code is encoded like
+/// [`CorpusRecorder::label`] does, such as `3%2B5%3D%3F.jpg` for `3+5=?`.
+pub fn read(path: &Path) -> Result<(String, LumaImage), String> {
+    let label = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .and_then(|s| s.split('_').next())
+        .filter(|s| !s.is_empty())
+        .ok_or("no label in file name")?;
+    let label = CorpusRecorder::text(label).ok_or("invalid label in file name")?;
+    let data = std::fs::read(path).map_err(|e| e.to_string())?;
+    let image = LumaImage::decode(&data).map_err(|e| e.to_string())?;
+    Ok((label, image))
+}
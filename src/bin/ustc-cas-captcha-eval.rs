@@ -0,0 +1,140 @@
+//! measure the captcha recognizer over a directory of labeled validate code images.
+//!
+//! Every image is named after the code it shows, optionally followed by `_` and anything
+//! else, such as `1234.jpg` or `1234_20230401.jpg`, the naming of
+//! `ustc_cas::CorpusRecorder`, which percent-encodes characters other than letters and
+//! digits. Exits with failure if the accuracy of whole codes is below `--min-accuracy`.
+//!
+//! With `cnn` feature, `--cnn` measures the bundled convolutional network instead of the
+//! templates, `--model FILE` another network, and `--compare` measures both the templates
+//! and the network over the same samples.
+
+mod common;
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+#[cfg(feature = "cnn")]
+use ustc_cas::captcha::CnnModel;
+use ustc_cas::captcha::{Evaluation, LumaImage, Recognizer, Templates};
+
+#[cfg(not(feature = "cnn"))]
+const USAGE: &str = "usage: ustc-cas-captcha-eval [--templates FILE] [--min-accuracy RATIO] \
+                     [--verbose] <samples dir>";
+#[cfg(feature = "cnn")]
+const USAGE: &str = "usage: ustc-cas-captcha-eval [--templates FILE] [--cnn | --model FILE] \
+                     [--compare] [--min-accuracy RATIO] [--verbose] <samples dir>";
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn run() -> Result<bool, String> {
+    let mut templates = Templates::bundled();
+    #[cfg(feature = "cnn")]
+    let mut model = None;
+    #[cfg(feature = "cnn")]
+    let mut compare = false;
+    let mut min_accuracy = 0.0;
+    let mut verbose = false;
+    let mut samples = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--templates" => {
+                let path = args.next().ok_or("--templates needs a file")?;
+                templates = Templates::load(&path).map_err(|e| format!("{path}: {e}"))?;
+            }
+            #[cfg(feature = "cnn")]
+            "--cnn" => model = Some(CnnModel::bundled()),
+            #[cfg(feature = "cnn")]
+            "--model" => {
+                let path = args.next().ok_or("--model needs a file")?;
+                model = Some(CnnModel::load(&path).map_err(|e| format!("{path}: {e}"))?);
+            }
+            #[cfg(feature = "cnn")]
+            "--compare" => compare = true,
+            "--min-accuracy" => {
+                min_accuracy = args
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .ok_or("--min-accuracy needs a number")?;
+            }
+            "--verbose" => verbose = true,
+            "-h" | "--help" => return Err(USAGE.into()),
+            _ if samples.is_none() => samples = Some(PathBuf::from(arg)),
+            _ => return Err(USAGE.into()),
+        }
+    }
+    let samples = samples.ok_or(USAGE)?;
+
+    let mut images = vec![];
+    for path in common::files(&samples)? {
+        match common::read(&path) {
+            Ok((label, image)) => images.push((path, label, image)),
+            Err(e) => eprintln!("skipped {}: {e}", path.display()),
+        }
+    }
+    if images.is_empty() {
+        return Err("no usable sample".into());
+    }
+
+    let template_recognizer = Recognizer::new().templates(templates);
+    #[allow(unused_mut)]
+    let mut recognizers = vec![("templates", template_recognizer)];
+    #[cfg(feature = "cnn")]
+    if compare || model.is_some() {
+        let model = model.unwrap_or_default();
+        if !compare {
+            recognizers.clear();
+        }
+        recognizers.push(("cnn", Recognizer::new().cnn(model)));
+    }
+
+    let mut passed = true;
+    for (i, (name, recognizer)) in recognizers.iter().enumerate() {
+        if recognizers.len() > 1 {
+            if i > 0 {
+                println!();
+            }
+            println!("== {name}");
+        }
+        let evaluation = evaluate(recognizer, &images, verbose);
+        print!("{evaluation}");
+        if evaluation.code_accuracy() < min_accuracy {
+            println!(
+                "code accuracy {:.2}% is below {:.2}%",
+                evaluation.code_accuracy() * 100.0,
+                min_accuracy * 100.0
+            );
+            passed = false;
+        }
+    }
+    Ok(passed)
+}
+
+fn evaluate(
+    recognizer: &Recognizer,
+    images: &[(PathBuf, String, LumaImage)],
+    verbose: bool,
+) -> Evaluation {
+    let mut evaluation = Evaluation::new();
+    for (path, label, image) in images {
+        let recognized = recognizer.recognize_luma(image).map(|r| r.text());
+        if verbose {
+            match &recognized {
+                Ok(code) if code == label => {}
+                Ok(code) => println!("{}: recognized as {code}", path.display()),
+                Err(e) => println!("{}: {e}", path.display()),
+            }
+        }
+        evaluation.add(label, recognized.ok().as_deref());
+    }
+    evaluation
+}
@@ -0,0 +1,124 @@
+//! build captcha templates from a directory of labeled validate code images.
+//!
+//! Every image is named after the code it shows, optionally followed by `_` and anything
+//! else, such as `1234.jpg` or `1234_20230401.jpg`, the naming of
+//! `ustc_cas::CorpusRecorder`, which percent-encodes characters other than letters and
+//! digits. The templates are written in the format read by
+//! `ustc_cas::captcha::Templates::load`.
+//!
+//! With `cnn` feature, `--cnn` trains a convolutional network instead, written in the
+//! format read by `ustc_cas::captcha::CnnModel::load`.
+
+mod common;
+
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+#[cfg(feature = "cnn")]
+use ustc_cas::captcha::CnnTrainer;
+use ustc_cas::captcha::{LumaImage, TemplateTrainer};
+
+#[cfg(not(feature = "cnn"))]
+const USAGE: &str = "usage: ustc-cas-captcha-train [--clusters N] <samples dir> <output file>";
+#[cfg(feature = "cnn")]
+const USAGE: &str = "usage: ustc-cas-captcha-train [--clusters N | --cnn [--epochs N]] \
+                     <samples dir> <output file>";
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run() -> Result<(), String> {
+    let mut clusters = 1;
+    #[cfg(feature = "cnn")]
+    let mut cnn = None;
+    let mut paths = vec![];
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--clusters" => {
+                clusters = args
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .ok_or("--clusters needs a number")?;
+            }
+            #[cfg(feature = "cnn")]
+            "--cnn" => cnn = Some(cnn.unwrap_or_else(CnnTrainer::new)),
+            #[cfg(feature = "cnn")]
+            "--epochs" => {
+                let epochs = args
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .ok_or("--epochs needs a number")?;
+                cnn = Some(cnn.unwrap_or_else(CnnTrainer::new).epochs(epochs));
+            }
+            "-h" | "--help" => return Err(USAGE.into()),
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+    let (samples, output) = match paths.as_slice() {
+        [samples, output] => (samples, output),
+        _ => return Err(USAGE.into()),
+    };
+
+    let files = common::files(samples)?;
+
+    #[cfg(feature = "cnn")]
+    if let Some(mut trainer) = cnn {
+        let used = add_all(&files, |image, label| trainer.add(image, label))?;
+        let model = trainer.build().map_err(|e| e.to_string())?;
+        write(output, &model.to_bytes().map_err(|e| e.to_string())?)?;
+        println!("{used} of {} samples used", files.len());
+        for (digit, count) in trainer.counts() {
+            println!("{digit}: {count} glyphs");
+        }
+        println!("model written to {}", output.display());
+        return Ok(());
+    }
+
+    let mut trainer = TemplateTrainer::new().clusters(clusters);
+    let used = add_all(&files, |image, label| trainer.add(image, label))?;
+    let templates = trainer.build().map_err(|e| e.to_string())?;
+    write(output, &templates.to_bytes().map_err(|e| e.to_string())?)?;
+
+    println!("{used} of {} samples used", files.len());
+    for (digit, count) in trainer.counts() {
+        println!("{digit}: {count} glyphs");
+    }
+    println!(
+        "{} templates written to {}",
+        templates.len(),
+        output.display()
+    );
+    Ok(())
+}
+
+/// Add every sample with `add`, and return how many were used.
+fn add_all<F, E>(files: &[PathBuf], mut add: F) -> Result<usize, String>
+where
+    F: FnMut(&LumaImage, &str) -> Result<(), E>,
+    E: ToString,
+{
+    let mut used = 0;
+    for path in files {
+        let sample = common::read(path)
+            .and_then(|(label, image)| add(&image, &label).map_err(|e| e.to_string()));
+        match sample {
+            Ok(()) => used += 1,
+            Err(e) => eprintln!("skipped {}: {e}", path.display()),
+        }
+    }
+    if used == 0 {
+        return Err("no usable sample".into());
+    }
+    Ok(used)
+}
+
+fn write(output: &Path, data: &[u8]) -> Result<(), String> {
+    std::fs::write(output, data).map_err(|e| format!("can not write {}: {e}", output.display()))
+}
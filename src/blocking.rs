@@ -1,20 +1,21 @@
-//! provide blocking version of [`get_ticket`](super::get_ticket)
+//! provide blocking version of [`get_ticket`](super::get_ticket),
+//! [`validate_ticket`](super::validate_ticket) and [`CasClient`](super::CasClient)
 //!
 //! Using this module requires enabling `blocking` feature.
 
 use super::*;
 use reqwest::blocking;
+use std::fmt::{Debug, Formatter};
+use std::time::Duration;
 
 /// log into USTC CAS System and get ticket value. blocking version of
 /// [`get_ticket`](super::get_ticket).
 ///
-/// # Panics
+/// This is a shortcut for [`CasClient::get_ticket`] on a default [`CasClient`] shared
+/// by the whole process.
 ///
-/// The function will panic if `validate-code` feature is disabled but validate code recognition
-/// is needed.
-///
-/// The function may panic if the CAS interface changed. This kind of panic is considered
-/// a bug and needs to be fixed.
+/// If neither `validate-code` nor `validate-code-lite` feature is enabled but validate
+/// code is requested, an error of kind [`ErrorKind::CaptchaRequired`] is returned.
 ///
 /// # Example
 /// ```rust
@@ -39,52 +40,530 @@ where
     P: AsRef<str>,
     S: AsRef<str>,
 {
-    static CLIENT: Lazy<blocking::Client> = Lazy::new(|| {
-        blocking::Client::builder()
-            .user_agent(USER_AGENT)
-            .cookie_store(true)
+    default_client()?.get_ticket(username, password, service_url)
+}
+
+/// validate a service ticket and get the user it was issued to. blocking version of
+/// [`validate_ticket`](super::validate_ticket).
+///
+/// This is a shortcut for [`CasClient::validate_ticket`] on the default [`CasClient`]
+/// shared by the whole process, validating with CAS 3.0.
+pub fn validate_ticket<T, S>(ticket: T, service_url: S) -> Result<Principal, CasError>
+where
+    T: AsRef<str>,
+    S: AsRef<str>,
+{
+    default_client()?.validate_ticket(ticket, service_url)
+}
+
+fn default_client() -> Result<&'static CasClient, CasError> {
+    static CLIENT: OnceCell<CasClient> = OnceCell::new();
+
+    CLIENT.get_or_try_init(|| CasClient::builder().build())
+}
+
+///
+/// A reusable client for logging into USTC CAS System. blocking version of
+/// [`CasClient`](super::CasClient).
+///
+/// Each call to `get_ticket` starts with an empty cookie jar, the same as the async version.
+///
+/// # Example
+/// ```rust,no_run
+/// use std::time::Duration;
+/// use ustc_cas::blocking::CasClient;
+///
+/// let client = CasClient::builder()
+///     .timeout(Duration::from_secs(10))
+///     .build()
+///     .unwrap();
+///
+/// match client.get_ticket("PB00000000", "12345678", "https://jw.ustc.edu.cn/ucas-sso/login") {
+///     Ok(s) => println!("ticket: {s}"),
+///     Err(e) => println!("Error: {e}"),
+/// }
+/// ```
+///
+#[derive(Clone)]
+pub struct CasClient {
+    client: blocking::Client,
+    login_url: String,
+    image_url: String,
+    validate_url: String,
+    max_attempts: u32,
+    min_confidence: f32,
+    max_refreshes: u32,
+    solver: Option<Arc<dyn CaptchaSolver>>,
+    recorder: Option<Arc<dyn CaptchaRecorder>>,
+}
+
+///
+/// A builder to construct a blocking [`CasClient`] with custom configuration.
+///
+/// Redirect policy and cookie handling are managed by the client itself and can not
+/// be changed. Cookies are never shared between logins, see [`CasClient`].
+///
+#[must_use]
+pub struct CasClientBuilder {
+    inner: blocking::ClientBuilder,
+    base_url: String,
+    protocol: CasProtocol,
+    max_attempts: u32,
+    min_confidence: f32,
+    max_refreshes: u32,
+    solver: Option<Arc<dyn CaptchaSolver>>,
+    recorder: Option<Arc<dyn CaptchaRecorder>>,
+}
+
+impl CasClient {
+    /// Create a [`CasClientBuilder`] with default configuration.
+    pub fn builder() -> CasClientBuilder {
+        CasClientBuilder::new()
+    }
+
+    /// log into USTC CAS System and get ticket value. blocking version of
+    /// [`CasClient::get_ticket`](super::CasClient::get_ticket).
+    ///
+    /// If validate code is requested but there is no captcha solver, an error of kind
+    /// [`ErrorKind::CaptchaRequired`] is returned, see [`CasClientBuilder::captcha_solver`]
+    /// and [`start_login`](Self::start_login).
+    ///
+    pub fn get_ticket<U, P, S>(
+        &self,
+        username: U,
+        password: P,
+        service_url: S,
+    ) -> Result<String, CasError>
+    where
+        U: AsRef<str>,
+        P: AsRef<str>,
+        S: AsRef<str>,
+    {
+        let username = username.as_ref();
+        let password = password.as_ref();
+        let service_url = service_url.as_ref();
+
+        let jar = Jar::default();
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            match self.login(&jar, username, password, service_url) {
+                Err(e)
+                    if e.kind() == ErrorKind::ValidateCodeIncorrect
+                        && attempts < self.max_attempts => {}
+                result => return result.map_err(|e| e.with_attempts(attempts)),
+            }
+        }
+    }
+
+    ///
+    /// Start a two-phase login, for solving the validate code without a captcha solver.
+    ///
+    /// If CAS does not ask for a validate code, the login finishes right away with
+    /// [`LoginStep::Ticket`]. Otherwise [`LoginStep::Captcha`] carries the validate code
+    /// image, and [`submit_captcha`](Self::submit_captcha) finishes the login with the
+    /// answer. The captcha solver of the client is never used.
+    ///
+    /// # Example
+    /// ```rust
+    /// use ustc_cas::blocking::CasClient;
+    /// use ustc_cas::LoginStep;
+    ///
+    /// # fn run() -> Result<(), ustc_cas::CasError> {
+    /// let client = CasClient::builder().build()?;
+    /// let service = "https://jw.ustc.edu.cn/ucas-sso/login";
+    ///
+    /// let ticket = match client.start_login("PB00000000", "12345678", service)? {
+    ///     LoginStep::Ticket(ticket) => ticket,
+    ///     LoginStep::Captcha(challenge) => {
+    ///         std::fs::write("validatecode.jpg", challenge.image()).unwrap();
+    ///         let mut code = String::new();
+    ///         std::io::stdin().read_line(&mut code).unwrap();
+    ///         client.submit_captcha(challenge, code.trim())?
+    ///     }
+    /// };
+    /// println!("ticket: {ticket}");
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    pub fn start_login<U, P, S>(
+        &self,
+        username: U,
+        password: P,
+        service_url: S,
+    ) -> Result<LoginStep, CasError>
+    where
+        U: AsRef<str>,
+        P: AsRef<str>,
+        S: AsRef<str>,
+    {
+        let jar = Jar::default();
+        let form = self.login_form(
+            &jar,
+            username.as_ref(),
+            password.as_ref(),
+            service_url.as_ref(),
+        )?;
+        if need_validate_code(&form) {
+            let image = self.validate_code_image(&jar)?;
+            Ok(LoginStep::Captcha(CaptchaChallenge { image, form, jar }))
+        } else {
+            self.submit(&jar, form).map(LoginStep::Ticket)
+        }
+    }
+
+    ///
+    /// Finish a login started by [`start_login`](Self::start_login) with the validate code
+    /// read from the challenge image.
+    ///
+    /// If CAS rejects the code, an error of kind [`ErrorKind::ValidateCodeIncorrect`] is
+    /// returned and the login has to be started over.
+    ///
+    pub fn submit_captcha<C: AsRef<str>>(
+        &self,
+        challenge: CaptchaChallenge,
+        code: C,
+    ) -> Result<String, CasError> {
+        let CaptchaChallenge {
+            image,
+            mut form,
+            jar,
+        } = challenge;
+        form.insert("LT".into(), code.as_ref().into());
+        let result = self.submit(&jar, form);
+        let code = code.as_ref();
+        record_captcha(&self.recorder, &image, code, code, &result);
+        result
+    }
+
+    /// validate a service ticket and get the user it was issued to. blocking version of
+    /// [`CasClient::validate_ticket`](super::CasClient::validate_ticket).
+    pub fn validate_ticket<T, S>(&self, ticket: T, service_url: S) -> Result<Principal, CasError>
+    where
+        T: AsRef<str>,
+        S: AsRef<str>,
+    {
+        let rsps = self
+            .client
+            .get(&self.validate_url)
+            .query(&[
+                ("service", service_url.as_ref()),
+                ("ticket", ticket.as_ref()),
+            ])
+            .send()?
+            .error_for_status()?;
+        parse_validation(&rsps.text()?)
+    }
+
+    /// a single login attempt, fetching a fresh login page and validate code.
+    fn login(
+        &self,
+        jar: &Jar,
+        username: &str,
+        password: &str,
+        service_url: &str,
+    ) -> Result<String, CasError> {
+        let mut form = self.login_form(jar, username, password, service_url)?;
+        let mut captcha = None;
+        if need_validate_code(&form) {
+            let solver = self
+                .solver
+                .as_ref()
+                .ok_or(CasError::new(ErrorKind::CaptchaRequired))?;
+            let (image, solution) = self.solve_captcha(jar, solver.as_ref())?;
+            form.insert("LT".into(), solution.code.clone());
+            captcha = Some((image, solution));
+        }
+        let result = self.submit(jar, form);
+        if let Some((image, solution)) = captcha {
+            record_captcha(
+                &self.recorder,
+                &image,
+                &solution.text,
+                &solution.code,
+                &result,
+            );
+        }
+        result
+    }
+
+    /// fetch and solve the validate code, fetching a new one while the solver is unsure or fails.
+    fn solve_captcha(
+        &self,
+        jar: &Jar,
+        solver: &dyn CaptchaSolver,
+    ) -> Result<(Vec<u8>, Solution), CasError> {
+        let mut refreshes = 0;
+        loop {
+            let image = self.validate_code_image(jar)?;
+            // a code the solver cannot read at all is as unsure as it gets
+            match solver.solve_with_text(&image) {
+                Ok(solution)
+                    if solution.confidence >= self.min_confidence
+                        || refreshes >= self.max_refreshes =>
+                {
+                    return Ok((image, solution));
+                }
+                Err(err) if refreshes >= self.max_refreshes => {
+                    return Err(CasError::from_solver(err));
+                }
+                _ => refreshes += 1,
+            }
+        }
+    }
+
+    /// fetch the login page and fill in the login form.
+    fn login_form(
+        &self,
+        jar: &Jar,
+        username: &str,
+        password: &str,
+        service_url: &str,
+    ) -> Result<HashMap<String, String>, CasError> {
+        let rsps = self
+            .send(
+                jar,
+                self.client
+                    .get(format!("{}?service={service_url}", self.login_url)),
+            )?
+            .error_for_status()?;
+
+        let text = rsps.text()?;
+        let cas_lt = get_cas_lt(&text)?.into();
+        let mut form = get_form(text)?;
+        form.insert("username".into(), username.into());
+        form.insert("password".into(), password.into());
+        form.insert("CAS_LT".into(), cas_lt);
+        Ok(form)
+    }
+
+    fn validate_code_image(&self, jar: &Jar) -> Result<Vec<u8>, CasError> {
+        let rsps = self
+            .send(jar, self.client.get(&self.image_url))?
+            .error_for_status()?;
+        Ok(rsps.bytes()?.to_vec())
+    }
+
+    /// post the login form and get the ticket.
+    fn submit(&self, jar: &Jar, mut form: HashMap<String, String>) -> Result<String, CasError> {
+        form.insert("button".into(), "".into());
+
+        let rsps = self
+            .send(jar, self.client.post(&self.login_url).form(&form))?
+            .error_for_status()?;
+
+        if rsps.headers().contains_key(LOCATION) {
+            match_ticket(rsps.headers())
+        } else {
+            Err(login_error(&rsps.text()?))
+        }
+    }
+
+    /// send a request with cookies from `jar`, and store the cookies set by the response back.
+    fn send(
+        &self,
+        jar: &Jar,
+        request: blocking::RequestBuilder,
+    ) -> Result<blocking::Response, CasError> {
+        let mut request = request.build()?;
+        if let Some(cookies) = jar.cookies(request.url()) {
+            request.headers_mut().insert(COOKIE, cookies);
+        }
+        let rsps = self.client.execute(request)?;
+        jar.set_cookies(&mut rsps.headers().get_all(SET_COOKIE).iter(), rsps.url());
+        Ok(rsps)
+    }
+}
+
+impl CasClientBuilder {
+    /// Create a builder with default configuration.
+    pub fn new() -> Self {
+        Self {
+            inner: blocking::Client::builder()
+                .user_agent(USER_AGENT)
+                .timeout(TIMEOUT),
+            base_url: BASE_URL.into(),
+            protocol: CasProtocol::V3,
+            max_attempts: 3,
+            min_confidence: 0.0,
+            max_refreshes: 3,
+            #[cfg(feature = "validate-code-lite")]
+            solver: Some(Arc::new(TemplateSolver)),
+            #[cfg(not(feature = "validate-code-lite"))]
+            solver: None,
+            recorder: None,
+        }
+    }
+
+    /// Build the [`CasClient`].
+    ///
+    /// Returns an error of kind [`ErrorKind::TlsError`] if the TLS backend fails to load,
+    /// or of kind [`ErrorKind::BuilderError`] if the configuration is invalid, e.g. the
+    /// user agent is not a valid header value.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if called from within an async runtime, the same as
+    /// `reqwest::blocking::ClientBuilder::build`.
+    pub fn build(self) -> Result<CasClient, CasError> {
+        let client = self
+            .inner
             .redirect(Policy::none())
             .build()
-            .unwrap()
-    });
+            .map_err(build_error)?;
+        Ok(CasClient {
+            client,
+            login_url: login_url(&self.base_url),
+            image_url: image_url(&self.base_url),
+            validate_url: validate_url(&self.base_url, self.protocol),
+            max_attempts: self.max_attempts,
+            min_confidence: self.min_confidence,
+            max_refreshes: self.max_refreshes,
+            solver: self.solver,
+            recorder: self.recorder,
+        })
+    }
+
+    /// Set the root url of the CAS server. Defaults to `https://passport.ustc.edu.cn`.
+    pub fn base_url<T: Into<String>>(mut self, url: T) -> Self {
+        self.base_url = url.into().trim_end_matches('/').into();
+        self
+    }
+
+    /// Set the protocol used by [`CasClient::validate_ticket`]. Defaults to
+    /// [`CasProtocol::V3`].
+    pub fn cas_protocol(mut self, protocol: CasProtocol) -> Self {
+        self.protocol = protocol;
+        self
+    }
+
+    /// Set how many times to try logging in when CAS rejects the validate code. Each try
+    /// starts over with a fresh login page and validate code. Defaults to 3.
+    ///
+    /// Other errors, such as a wrong password, are never retried. `0` is treated as `1`.
+    pub fn max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = attempts.max(1);
+        self
+    }
 
-    let username = username.as_ref();
-    let password = password.as_ref();
-    let service_url = service_url.as_ref();
+    /// Fetch a new validate code, in the same CAS session, instead of submitting an answer
+    /// the captcha solver is less sure about than `confidence`, as reported by
+    /// [`CaptchaSolver::solve_with_confidence`]. An unsure answer is submitted anyway
+    /// once [`max_captcha_refreshes`](Self::max_captcha_refreshes) is used up, since only
+    /// the latest validate code of a session is valid.
+    ///
+    /// Refetching costs one request, while a rejected answer costs a whole login attempt
+    /// and counts towards account lockout. Defaults to `0.0`, never refetching.
+    pub fn min_captcha_confidence(mut self, confidence: f32) -> Self {
+        self.min_confidence = confidence;
+        self
+    }
+
+    /// Set how many times a validate code may be refetched for each login attempt, see
+    /// [`min_captcha_confidence`](Self::min_captcha_confidence). A validate code the
+    /// solver fails to read is refetched too, and the login fails only once the refreshes
+    /// are used up. Defaults to 3.
+    pub fn max_captcha_refreshes(mut self, refreshes: u32) -> Self {
+        self.max_refreshes = refreshes;
+        self
+    }
 
-    let rsps = CLIENT
-        .get(format!("{URL}?service={service_url}"))
-        .send()?
-        .error_for_status()
-        .unwrap();
+    /// Set the solver recognizing validate code. Defaults to [`TemplateSolver`] if
+    /// `validate-code` or `validate-code-lite` feature is enabled, otherwise there is no
+    /// solver.
+    pub fn captcha_solver<T: CaptchaSolver + 'static>(mut self, solver: T) -> Self {
+        self.solver = Some(Arc::new(solver));
+        self
+    }
 
-    let text = rsps.text().unwrap();
-    let cas_lt = get_cas_lt(&text)?.into();
-    let mut form = get_form(text)?;
-    form.insert("username".into(), username.into());
-    form.insert("password".into(), password.into());
-    form.insert("CAS_LT".into(), cas_lt);
+    /// Set a recorder told about every validate code CAS accepts or rejects, such as a
+    /// [`CorpusRecorder`] collecting images for training. There is none by default.
+    pub fn captcha_recorder<T: CaptchaRecorder + 'static>(mut self, recorder: T) -> Self {
+        self.recorder = Some(Arc::new(recorder));
+        self
+    }
 
-    #[cfg(feature = "validate-code")]
-    if form["showCode"] == "1" {
-        let rsps = CLIENT.get(IMAGE_URL).send()?.error_for_status().unwrap();
-        let code = validate_code::get_validatecode(rsps.bytes().unwrap());
-        form.insert("LT".into(), code);
+    /// Set the `User-Agent` header. Defaults to a desktop browser.
+    pub fn user_agent<T: AsRef<str>>(mut self, value: T) -> Self {
+        self.inner = self.inner.user_agent(value.as_ref());
+        self
     }
 
-    #[cfg(not(feature = "validate-code"))]
-    if form["showCode"] == "1" {
-        panic!("validate code needed but validate-code feature not enabled");
+    /// Set a timeout for each request, from connecting until the response body has finished.
+    /// Defaults to 30 seconds.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.inner = self.inner.timeout(timeout);
+        self
     }
 
-    form.insert("button".into(), "".into());
+    /// Set a timeout for only the connect phase of each request. Not set by default, so
+    /// only [`timeout`](Self::timeout) applies.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.inner = self.inner.connect_timeout(timeout);
+        self
+    }
 
-    let rsps = CLIENT
-        .post(URL)
-        .form(&form)
-        .send()?
-        .error_for_status()
-        .unwrap();
+    /// Add a proxy to the list of proxies used.
+    pub fn proxy(mut self, proxy: Proxy) -> Self {
+        self.inner = self.inner.proxy(proxy);
+        self
+    }
+
+    /// Disable all proxies, including the system ones.
+    pub fn no_proxy(mut self) -> Self {
+        self.inner = self.inner.no_proxy();
+        self
+    }
+
+    /// Add a custom root certificate to trust.
+    #[cfg(any(feature = "native-tls", feature = "rustls-tls"))]
+    pub fn add_root_certificate(mut self, cert: reqwest::Certificate) -> Self {
+        self.inner = self.inner.add_root_certificate(cert);
+        self
+    }
 
-    match_ticket(rsps.headers())
+    /// Control whether invalid certificates are accepted. Dangerous, use with care.
+    #[cfg(any(feature = "native-tls", feature = "rustls-tls"))]
+    pub fn danger_accept_invalid_certs(mut self, accept: bool) -> Self {
+        self.inner = self.inner.danger_accept_invalid_certs(accept);
+        self
+    }
+}
+
+impl Debug for CasClient {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CasClient")
+            .field("client", &self.client)
+            .field("login_url", &self.login_url)
+            .field("image_url", &self.image_url)
+            .field("validate_url", &self.validate_url)
+            .field("max_attempts", &self.max_attempts)
+            .field("min_confidence", &self.min_confidence)
+            .field("max_refreshes", &self.max_refreshes)
+            .field("solver", &self.solver.as_ref().map(|_| ".."))
+            .field("recorder", &self.recorder.as_ref().map(|_| ".."))
+            .finish()
+    }
+}
+
+impl Debug for CasClientBuilder {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CasClientBuilder")
+            .field("inner", &self.inner)
+            .field("base_url", &self.base_url)
+            .field("protocol", &self.protocol)
+            .field("max_attempts", &self.max_attempts)
+            .field("min_confidence", &self.min_confidence)
+            .field("max_refreshes", &self.max_refreshes)
+            .field("solver", &self.solver.as_ref().map(|_| ".."))
+            .field("recorder", &self.recorder.as_ref().map(|_| ".."))
+            .finish()
+    }
+}
+
+impl Default for CasClientBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
 }
@@ -0,0 +1,76 @@
+use std::sync::Arc;
+
+///
+/// The characters a validate code may be made of.
+///
+/// A [`Recognizer`](super::Recognizer) with an alphabet only answers with characters in
+/// it, any template or model label outside the alphabet is ignored. This way one set of
+/// templates holding digits, letters and operators serves captchas of every kind.
+///
+/// Labels are case sensitive, so an alphabet of letters should hold both cases if the
+/// templates do.
+///
+/// # Example
+/// ```rust
+/// use ustc_cas::captcha::{Alphabet, Recognizer};
+///
+/// let hex = Recognizer::new().alphabet(Alphabet::new("0123456789ABCDEF"));
+/// let letters = Recognizer::new().alphabet(Alphabet::letters());
+/// ```
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Alphabet {
+    chars: Arc<Vec<char>>,
+}
+
+impl Alphabet {
+    /// An alphabet of the characters of `chars`, duplicates are ignored.
+    pub fn new(chars: &str) -> Self {
+        let mut list: Vec<char> = vec![];
+        for c in chars.chars() {
+            if !list.contains(&c) {
+                list.push(c);
+            }
+        }
+        Self {
+            chars: Arc::new(list),
+        }
+    }
+
+    /// Digits `0` to `9`, the characters of the USTC validate code.
+    pub fn digits() -> Self {
+        Self::new("0123456789")
+    }
+
+    /// Letters `A` to `Z` and `a` to `z`.
+    pub fn letters() -> Self {
+        Self::new("ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz")
+    }
+
+    /// Digits and letters.
+    pub fn alphanumeric() -> Self {
+        Self::new("0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz")
+    }
+
+    /// Digits, the operators `+`, `-`, `*`, `x` and `/`, and `=` and `?`, the characters
+    /// of an arithmetic code such as `3+5=?`.
+    pub fn arithmetic() -> Self {
+        Self::new("0123456789+-*x/=?")
+    }
+
+    /// Whether `c` is in the alphabet.
+    pub fn contains(&self, c: char) -> bool {
+        self.chars.contains(&c)
+    }
+
+    /// The characters of the alphabet, in the order given.
+    pub fn chars(&self) -> &[char] {
+        &self.chars
+    }
+}
+
+impl Default for Alphabet {
+    fn default() -> Self {
+        Self::digits()
+    }
+}
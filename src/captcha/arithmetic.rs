@@ -0,0 +1,55 @@
+use super::{CaptchaError, CaptchaErrorKind};
+
+///
+/// Evaluate an arithmetic code such as `3+5=?` or `12x3`.
+///
+/// The expression is made of non-negative integers and the operators `+`, `-`, `*` (or
+/// `x`) and `/`, with the usual precedence, optionally followed by `=` and `?`. Division
+/// must be exact. An error of kind [`Expression`](CaptchaErrorKind::Expression) is
+/// returned for anything else, and on overflow.
+///
+pub(crate) fn evaluate(text: &str) -> Result<i64, CaptchaError> {
+    let error = |msg: &str| {
+        CaptchaError::with_source(CaptchaErrorKind::Expression, format!("{msg} in \"{text}\""))
+    };
+
+    let expression = text.strip_suffix('?').unwrap_or(text);
+    let expression = expression.strip_suffix('=').unwrap_or(expression);
+
+    // the numbers, and the operators between them
+    let mut numbers = vec![];
+    let mut operators = vec![];
+    let mut start = 0;
+    for (i, c) in expression.char_indices() {
+        if !c.is_ascii_digit() {
+            numbers.push(&expression[start..i]);
+            operators.push(c);
+            start = i + c.len_utf8();
+        }
+    }
+    numbers.push(&expression[start..]);
+    let numbers = numbers
+        .into_iter()
+        .map(|n| match n {
+            "" => Err(error("number expected")),
+            n => n.parse::<i64>().map_err(|_| error("number too large")),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // the sum of the terms before the current one
+    let mut sum = 0i64;
+    let mut term = numbers[0];
+    for (&operator, &n) in operators.iter().zip(&numbers[1..]) {
+        match operator {
+            '+' | '-' => {
+                sum = sum.checked_add(term).ok_or_else(|| error("overflow"))?;
+                term = if operator == '+' { n } else { -n };
+            }
+            '*' | 'x' => term = term.checked_mul(n).ok_or_else(|| error("overflow"))?,
+            '/' if n != 0 && term % n == 0 => term /= n,
+            '/' => return Err(error("inexact division")),
+            c => return Err(error(&format!("unexpected character {c:?}"))),
+        }
+    }
+    sum.checked_add(term).ok_or_else(|| error("overflow"))
+}
@@ -0,0 +1,285 @@
+//! forward and backward passes of the network, on flat `f32` buffers.
+//!
+//! Feature maps are stored channel by channel, row by row.
+
+/// height and width of the input window.
+pub(crate) const INPUT_HEIGHT: usize = 24;
+pub(crate) const INPUT_WIDTH: usize = 16;
+/// channels of the two convolutions.
+const CHANNELS_1: usize = 8;
+const CHANNELS_2: usize = 16;
+/// inputs of the dense layer, after two 2x2 poolings.
+const FLAT: usize = CHANNELS_2 * (INPUT_HEIGHT / 4) * (INPUT_WIDTH / 4);
+
+/// The number of parameters of a network telling `classes` classes apart.
+pub(crate) fn parameter_count(classes: usize) -> usize {
+    Layout::new(classes).end
+}
+
+/// Where each parameter tensor lies in the flat parameter buffer.
+pub(crate) struct Layout {
+    conv1: usize,
+    bias1: usize,
+    conv2: usize,
+    bias2: usize,
+    dense: usize,
+    bias3: usize,
+    end: usize,
+    classes: usize,
+}
+
+impl Layout {
+    pub(crate) fn new(classes: usize) -> Self {
+        let conv1 = 0;
+        let bias1 = conv1 + CHANNELS_1 * 9;
+        let conv2 = bias1 + CHANNELS_1;
+        let bias2 = conv2 + CHANNELS_2 * CHANNELS_1 * 9;
+        let dense = bias2 + CHANNELS_2;
+        let bias3 = dense + classes * FLAT;
+        Self {
+            conv1,
+            bias1,
+            conv2,
+            bias2,
+            dense,
+            bias3,
+            end: bias3 + classes,
+            classes,
+        }
+    }
+
+    /// The number of inputs of every weight, for initialization. Biases have none.
+    pub(crate) fn fan_in(&self, index: usize) -> Option<usize> {
+        if index < self.bias1 {
+            Some(9)
+        } else if (self.conv2..self.bias2).contains(&index) {
+            Some(CHANNELS_1 * 9)
+        } else if (self.dense..self.bias3).contains(&index) {
+            Some(FLAT)
+        } else {
+            None
+        }
+    }
+}
+
+/// Everything computed by a forward pass, kept for the backward pass.
+pub(crate) struct Forward {
+    a1: Vec<f32>,
+    p1: Vec<f32>,
+    p1_from: Vec<usize>,
+    a2: Vec<f32>,
+    p2: Vec<f32>,
+    p2_from: Vec<usize>,
+    /// probability of every class.
+    pub(crate) output: Vec<f32>,
+}
+
+pub(crate) fn forward(params: &[f32], layout: &Layout, input: &[f32]) -> Forward {
+    let (h, w) = (INPUT_HEIGHT, INPUT_WIDTH);
+    let a1 = convolve(
+        input,
+        1,
+        h,
+        w,
+        &params[layout.conv1..layout.bias1],
+        &params[layout.bias1..layout.conv2],
+    );
+    let (p1, p1_from) = pool(&a1, CHANNELS_1, h, w);
+    let (h, w) = (h / 2, w / 2);
+    let a2 = convolve(
+        &p1,
+        CHANNELS_1,
+        h,
+        w,
+        &params[layout.conv2..layout.bias2],
+        &params[layout.bias2..layout.dense],
+    );
+    let (p2, p2_from) = pool(&a2, CHANNELS_2, h, w);
+
+    let weights = &params[layout.dense..layout.bias3];
+    let biases = &params[layout.bias3..layout.end];
+    let logits: Vec<f32> = (0..layout.classes)
+        .map(|c| {
+            let row = &weights[c * FLAT..(c + 1) * FLAT];
+            biases[c] + row.iter().zip(&p2).map(|(w, x)| w * x).sum::<f32>()
+        })
+        .collect();
+
+    Forward {
+        a1,
+        p1,
+        p1_from,
+        a2,
+        p2,
+        p2_from,
+        output: softmax(&logits),
+    }
+}
+
+/// Add the gradient of the cross entropy loss of `forward` against class `target` to
+/// `gradient`.
+pub(crate) fn backward(
+    params: &[f32],
+    layout: &Layout,
+    input: &[f32],
+    forward: &Forward,
+    target: usize,
+    gradient: &mut [f32],
+) {
+    // softmax with cross entropy
+    let mut d_logits = forward.output.clone();
+    d_logits[target] -= 1.0;
+
+    let mut d_p2 = vec![0.0; FLAT];
+    for (c, &d) in d_logits.iter().enumerate() {
+        gradient[layout.bias3 + c] += d;
+        let row = layout.dense + c * FLAT;
+        for i in 0..FLAT {
+            gradient[row + i] += d * forward.p2[i];
+            d_p2[i] += d * params[row + i];
+        }
+    }
+
+    let (h, w) = (INPUT_HEIGHT / 2, INPUT_WIDTH / 2);
+    let d_a2 = unpool(&d_p2, &forward.p2_from, &forward.a2);
+    let d_p1 = convolve_backward(
+        &forward.p1,
+        CHANNELS_1,
+        h,
+        w,
+        &params[layout.conv2..layout.bias2],
+        &d_a2,
+        &mut gradient[layout.conv2..layout.dense],
+        true,
+    );
+
+    let (h, w) = (INPUT_HEIGHT, INPUT_WIDTH);
+    let d_a1 = unpool(&d_p1, &forward.p1_from, &forward.a1);
+    convolve_backward(
+        input,
+        1,
+        h,
+        w,
+        &params[layout.conv1..layout.bias1],
+        &d_a1,
+        &mut gradient[layout.conv1..layout.conv2],
+        false,
+    );
+}
+
+/// 3x3 convolution with zero padding, followed by ReLU.
+fn convolve(
+    input: &[f32],
+    channels: usize,
+    h: usize,
+    w: usize,
+    weights: &[f32],
+    biases: &[f32],
+) -> Vec<f32> {
+    let mut out = Vec::with_capacity(biases.len() * h * w);
+    for (o, &bias) in biases.iter().enumerate() {
+        for y in 0..h {
+            for x in 0..w {
+                let mut sum = bias;
+                for i in 0..channels {
+                    let kernel = &weights[(o * channels + i) * 9..][..9];
+                    for (k, &weight) in kernel.iter().enumerate() {
+                        if let Some(v) = at(input, i, h, w, y + k / 3, x + k % 3) {
+                            sum += weight * v;
+                        }
+                    }
+                }
+                out.push(sum.max(0.0));
+            }
+        }
+    }
+    out
+}
+
+/// Gradient of [`convolve`]: accumulate the gradient of weights and biases, laid out as
+/// `weights` followed by the biases, and return the gradient of the input if asked.
+#[allow(clippy::too_many_arguments)]
+fn convolve_backward(
+    input: &[f32],
+    channels: usize,
+    h: usize,
+    w: usize,
+    weights: &[f32],
+    d_out: &[f32],
+    gradient: &mut [f32],
+    input_gradient: bool,
+) -> Vec<f32> {
+    let outputs = d_out.len() / (h * w);
+    let (d_weights, d_biases) = gradient.split_at_mut(weights.len());
+    let mut d_input = vec![0.0; if input_gradient { input.len() } else { 0 }];
+    for o in 0..outputs {
+        for y in 0..h {
+            for x in 0..w {
+                let d = d_out[(o * h + y) * w + x];
+                if d == 0.0 {
+                    continue;
+                }
+                d_biases[o] += d;
+                for i in 0..channels {
+                    let base = (o * channels + i) * 9;
+                    for k in 0..9 {
+                        let (sy, sx) = (y + k / 3, x + k % 3);
+                        if let Some(v) = at(input, i, h, w, sy, sx) {
+                            d_weights[base + k] += d * v;
+                            if input_gradient {
+                                d_input[(i * h + sy - 1) * w + sx - 1] += d * weights[base + k];
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+    d_input
+}
+
+/// The value at (`x - 1`, `y - 1`) of channel `c`, `None` in the padding.
+fn at(input: &[f32], c: usize, h: usize, w: usize, y: usize, x: usize) -> Option<f32> {
+    (y >= 1 && x >= 1 && y <= h && x <= w).then(|| input[(c * h + y - 1) * w + x - 1])
+}
+
+/// 2x2 max pooling, also returning where every maximum came from.
+fn pool(input: &[f32], channels: usize, h: usize, w: usize) -> (Vec<f32>, Vec<usize>) {
+    let mut out = Vec::with_capacity(input.len() / 4);
+    let mut from = Vec::with_capacity(input.len() / 4);
+    for c in 0..channels {
+        for y in (0..h).step_by(2) {
+            for x in (0..w).step_by(2) {
+                let best = [(y, x), (y, x + 1), (y + 1, x), (y + 1, x + 1)]
+                    .iter()
+                    .map(|&(y, x)| (c * h + y) * w + x)
+                    .fold(None, |best: Option<usize>, i| match best {
+                        Some(b) if input[b] >= input[i] => Some(b),
+                        _ => Some(i),
+                    })
+                    .unwrap();
+                out.push(input[best]);
+                from.push(best);
+            }
+        }
+    }
+    (out, from)
+}
+
+/// Gradient of [`pool`] and of the ReLU before it.
+fn unpool(d_out: &[f32], from: &[usize], activations: &[f32]) -> Vec<f32> {
+    let mut d_input = vec![0.0; activations.len()];
+    for (&d, &i) in d_out.iter().zip(from) {
+        if activations[i] > 0.0 {
+            d_input[i] += d;
+        }
+    }
+    d_input
+}
+
+fn softmax(logits: &[f32]) -> Vec<f32> {
+    let max = logits.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let exp: Vec<f32> = logits.iter().map(|l| (l - max).exp()).collect();
+    let sum: f32 = exp.iter().sum();
+    exp.iter().map(|e| e / sum).collect()
+}
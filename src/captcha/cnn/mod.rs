@@ -0,0 +1,196 @@
+mod layers;
+mod train;
+
+pub use train::CnnTrainer;
+
+use super::knn::DigitMatch;
+use super::segment::Segment;
+use super::template::THRESHOLD;
+use super::{Alphabet, CaptchaError, CaptchaErrorKind, LumaImage};
+use layers::{Layout, INPUT_HEIGHT, INPUT_WIDTH};
+use once_cell::sync::Lazy;
+use std::path::Path;
+use std::sync::Arc;
+
+const MAGIC: &[u8; 4] = b"UCNN";
+const VERSION: u8 = 1;
+
+/// Trained by `ustc-cas-captcha-train --cnn` on 300 plain synthetic images drawn from the
+/// glyphs of the bundled templates. It has not seen a real validate code.
+static BUNDLED: Lazy<CnnModel> = Lazy::new(|| {
+    CnnModel::from_bytes(include_bytes!("model.bin")).expect("bundled model is valid")
+});
+
+///
+/// A small convolutional network classifying the digits of validate codes, an
+/// alternative to [`Templates`](super::Templates) for [`Recognizer`](super::Recognizer).
+///
+/// The network looks at a 16x24 window from the top left corner of each digit found by
+/// segmentation, through two 3x3 convolutions with ReLU and 2x2 max pooling, of 8 and 16
+/// channels, and a dense layer with softmax over the labels. It runs on the CPU and takes
+/// a few thousand parameters, so recognizing a code is about as fast as with templates.
+///
+/// A model for the USTC validate code is bundled with the crate. Models are trained from
+/// labeled images with [`CnnTrainer`], and loaded at runtime with [`CnnModel::load`].
+///
+/// Using this type requires enabling `cnn` feature.
+///
+/// # Format
+/// Models are stored in a small binary file, all numbers little endian:
+///
+/// - magic `UCNN`, then the format version `1`, input height `24`, input width `16` and
+///   the number of labels, one byte each.
+/// - every label as an ASCII byte.
+/// - every parameter as an `f32`: the weights and biases of the first convolution, of the
+///   second convolution, then of the dense layer, one row of weights per label.
+///
+/// # Example
+/// ```rust
+/// use ustc_cas::captcha::{CnnModel, Recognizer};
+///
+/// # fn run(image: &[u8]) -> Result<(), ustc_cas::captcha::CaptchaError> {
+/// let recognizer = Recognizer::new().cnn(CnnModel::bundled());
+/// println!("{}", recognizer.recognize(image)?.code());
+/// # Ok(())
+/// # }
+/// ```
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct CnnModel {
+    labels: Arc<Vec<char>>,
+    params: Arc<Vec<f32>>,
+}
+
+impl CnnModel {
+    /// The model bundled with the crate.
+    pub fn bundled() -> Self {
+        BUNDLED.clone()
+    }
+
+    /// Parse a model in the format described above.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, CaptchaError> {
+        let error = |msg: &str| CaptchaError::with_source(CaptchaErrorKind::Model, msg);
+
+        let (header, data) = data.split_at(data.len().min(8));
+        if header.len() < 8 || &header[..4] != MAGIC {
+            return Err(error("not a model file"));
+        }
+        if header[4] != VERSION {
+            return Err(error("unsupported model file version"));
+        }
+        if (header[5], header[6]) != (INPUT_HEIGHT as u8, INPUT_WIDTH as u8) {
+            return Err(error("unsupported input size"));
+        }
+
+        let count = header[7] as usize;
+        if count < 2 || data.len() != count + layers::parameter_count(count) * 4 {
+            return Err(error("wrong number of labels or parameters"));
+        }
+        let (labels, params) = data.split_at(count);
+        let labels: Vec<char> = labels.iter().map(|&b| b as char).collect();
+        if !labels.iter().all(char::is_ascii_graphic) {
+            return Err(error("model label is not a printable character"));
+        }
+        let params: Vec<f32> = params
+            .chunks(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        if !params.iter().all(|p| p.is_finite()) {
+            return Err(error("model parameter is not finite"));
+        }
+        Ok(Self::from_parts(labels, params))
+    }
+
+    pub(crate) fn from_parts(labels: Vec<char>, params: Vec<f32>) -> Self {
+        Self {
+            labels: Arc::new(labels),
+            params: Arc::new(params),
+        }
+    }
+
+    /// Read a model from a file, see [`CnnModel::from_bytes`].
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, CaptchaError> {
+        let data = std::fs::read(path)
+            .map_err(|e| CaptchaError::with_source(CaptchaErrorKind::Model, e))?;
+        Self::from_bytes(&data)
+    }
+
+    /// Write the model in the format described above.
+    ///
+    /// Returns an error of kind [`Model`](CaptchaErrorKind::Model) if there are more than
+    /// 255 labels, or a label is not printable ASCII, which the format cannot hold.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, CaptchaError> {
+        let error = |msg: &str| CaptchaError::with_source(CaptchaErrorKind::Model, msg);
+
+        let count = u8::try_from(self.labels.len()).map_err(|_| error("more than 255 labels"))?;
+        if !self.labels.iter().all(char::is_ascii_graphic) {
+            return Err(error("model label is not a printable character"));
+        }
+        let mut data = MAGIC.to_vec();
+        data.extend([VERSION, INPUT_HEIGHT as u8, INPUT_WIDTH as u8, count]);
+        data.extend(self.labels.iter().map(|&c| c as u8));
+        for p in self.params.iter() {
+            data.extend(p.to_le_bytes());
+        }
+        Ok(data)
+    }
+
+    /// The labels the model tells apart, in the order of its outputs.
+    pub fn labels(&self) -> &[char] {
+        &self.labels
+    }
+
+    /// Classify the digit in `segment` of a preprocessed image. The score is the
+    /// probability of the label, and the confidence its margin over the next one. Labels
+    /// outside `alphabet` are left out, and the probabilities of the others scaled to sum
+    /// to one.
+    pub(crate) fn classify(
+        &self,
+        image: &LumaImage,
+        segment: &Segment,
+        alphabet: Option<&Alphabet>,
+    ) -> DigitMatch {
+        let (left, top) = (segment.x as i32 - 1, segment.y as i32 - 1);
+        let input = window(image, left, top, INPUT_WIDTH, INPUT_HEIGHT);
+        let layout = Layout::new(self.labels.len());
+        let output = layers::forward(&self.params, &layout, &input).output;
+
+        let mut ranked: Vec<(usize, f32)> = output
+            .into_iter()
+            .enumerate()
+            .filter(|&(i, _)| alphabet.map_or(true, |a| a.contains(self.labels[i])))
+            .collect();
+        let sum: f32 = ranked.iter().map(|r| r.1).sum();
+        if sum > 0.0 {
+            ranked.iter_mut().for_each(|r| r.1 /= sum);
+        }
+        ranked.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        let (best, score) = ranked[0];
+        DigitMatch {
+            value: self.labels[best],
+            score,
+            confidence: score - ranked.get(1).map_or(0.0, |r| r.1),
+        }
+    }
+}
+
+impl Default for CnnModel {
+    fn default() -> Self {
+        Self::bundled()
+    }
+}
+
+/// The window of `width` by `height` with its top left corner at (`left`, `top`),
+/// `1.0` for ink.
+fn window(image: &LumaImage, left: i32, top: i32, width: usize, height: usize) -> Vec<f32> {
+    let mut input = Vec::with_capacity(width * height);
+    for y in top..top + height as i32 {
+        for x in left..left + width as i32 {
+            let inside =
+                x >= 0 && y >= 0 && (x as u32) < image.width() && (y as u32) < image.height();
+            let ink = inside && image.get(x as u32, y as u32) < THRESHOLD;
+            input.push(if ink { 1.0 } else { 0.0 });
+        }
+    }
+    input
+}
@@ -0,0 +1,209 @@
+use super::layers::{self, Layout, INPUT_HEIGHT, INPUT_WIDTH};
+use super::{window, CnnModel};
+use crate::captcha::train::labeled_segments;
+use crate::captcha::{CaptchaError, CaptchaErrorKind, LumaImage, Pipeline};
+use std::collections::BTreeMap;
+
+/// how far, in pixels, samples are shifted at random while training.
+const JITTER: usize = 1;
+const SAMPLE_HEIGHT: usize = INPUT_HEIGHT + 2 * JITTER;
+const SAMPLE_WIDTH: usize = INPUT_WIDTH + 2 * JITTER;
+const BATCH: usize = 16;
+const MOMENTUM: f32 = 0.9;
+
+///
+/// Train a [`CnnModel`] from labeled captcha images.
+///
+/// Samples are preprocessed and segmented like with
+/// [`TemplateTrainer`](crate::captcha::TemplateTrainer), then the network is trained by
+/// stochastic gradient descent with momentum, shifting every digit by up to a pixel at
+/// random so the model tolerates imprecise segmentation. Training is deterministic, the
+/// same samples always give the same model.
+///
+/// The `ustc-cas-captcha-train` binary trains a model with `--cnn`, when both `train` and
+/// `cnn` features are enabled.
+///
+/// # Example
+/// ```rust
+/// use ustc_cas::captcha::{CnnTrainer, LumaImage, Recognizer};
+///
+/// # fn run(samples: Vec<(LumaImage, String)>) -> Result<(), ustc_cas::captcha::CaptchaError> {
+/// let mut trainer = CnnTrainer::new().epochs(20);
+/// for (image, label) in &samples {
+///     trainer.add(image, label)?;
+/// }
+/// let recognizer = Recognizer::new().cnn(trainer.build()?);
+/// # Ok(())
+/// # }
+/// ```
+///
+#[derive(Clone, Debug)]
+pub struct CnnTrainer {
+    pipeline: Pipeline,
+    epochs: usize,
+    learning_rate: f32,
+    samples: Vec<(Vec<f32>, char)>,
+}
+
+impl CnnTrainer {
+    /// Create a trainer with the default pipeline, 30 epochs and a learning rate of 0.01.
+    pub fn new() -> Self {
+        Self {
+            pipeline: Pipeline::default(),
+            epochs: 30,
+            learning_rate: 0.01,
+            samples: vec![],
+        }
+    }
+
+    /// Set the preprocessing pipeline. It should be the one the model is going to be
+    /// used with. Defaults to [`Pipeline::default`].
+    #[must_use]
+    pub fn pipeline(mut self, pipeline: Pipeline) -> Self {
+        self.pipeline = pipeline;
+        self
+    }
+
+    /// Set how many times every sample is seen while training. Defaults to 30.
+    #[must_use]
+    pub fn epochs(mut self, epochs: usize) -> Self {
+        self.epochs = epochs;
+        self
+    }
+
+    /// Set the step size of gradient descent. Defaults to 0.01.
+    #[must_use]
+    pub fn learning_rate(mut self, rate: f32) -> Self {
+        self.learning_rate = rate;
+        self
+    }
+
+    /// Add a sample showing the code `label`.
+    ///
+    /// Returns the same errors as [`TemplateTrainer::add`](crate::captcha::TemplateTrainer::add),
+    /// in which case nothing is added.
+    pub fn add(&mut self, image: &LumaImage, label: &str) -> Result<(), CaptchaError> {
+        let (image, segments) = labeled_segments(&self.pipeline, image, label)?;
+        for (s, value) in segments.iter().zip(label.chars()) {
+            // a window larger than the input, cut at random offsets while training
+            let sample = window(
+                &image,
+                s.x as i32 - 1 - JITTER as i32,
+                s.y as i32 - 1 - JITTER as i32,
+                SAMPLE_WIDTH,
+                SAMPLE_HEIGHT,
+            );
+            self.samples.push((sample, value));
+        }
+        Ok(())
+    }
+
+    /// The number of digits collected for each label.
+    pub fn counts(&self) -> BTreeMap<char, usize> {
+        let mut counts = BTreeMap::new();
+        for (_, value) in &self.samples {
+            *counts.entry(*value).or_default() += 1;
+        }
+        counts
+    }
+
+    /// Train a model on the samples added so far.
+    ///
+    /// Returns an error of kind [`Model`](CaptchaErrorKind::Model) if the samples have
+    /// fewer than two labels.
+    pub fn build(&self) -> Result<CnnModel, CaptchaError> {
+        let labels: Vec<char> = self.counts().into_keys().collect();
+        if labels.len() < 2 || labels.len() > u8::MAX as usize {
+            return Err(CaptchaError::with_source(
+                CaptchaErrorKind::Model,
+                "samples of 2 to 255 labels needed",
+            ));
+        }
+        let layout = Layout::new(labels.len());
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+
+        let mut params: Vec<f32> = (0..layers::parameter_count(labels.len()))
+            .map(|i| match layout.fan_in(i) {
+                // He initialization, uniform
+                Some(fan_in) => (rng.unit() * 2.0 - 1.0) * (6.0 / fan_in as f32).sqrt(),
+                None => 0.0,
+            })
+            .collect();
+        let mut velocity = vec![0.0; params.len()];
+        let mut gradient = vec![0.0; params.len()];
+
+        let targets: Vec<usize> = self
+            .samples
+            .iter()
+            .map(|(_, value)| labels.iter().position(|l| l == value).unwrap())
+            .collect();
+        let mut order: Vec<usize> = (0..self.samples.len()).collect();
+        let mut input = vec![0.0; INPUT_HEIGHT * INPUT_WIDTH];
+        for _ in 0..self.epochs {
+            rng.shuffle(&mut order);
+            for batch in order.chunks(BATCH) {
+                gradient.iter_mut().for_each(|g| *g = 0.0);
+                for &i in batch {
+                    let (dx, dy) = (rng.below(2 * JITTER + 1), rng.below(2 * JITTER + 1));
+                    cut(&self.samples[i].0, dx, dy, &mut input);
+                    let forward = layers::forward(&params, &layout, &input);
+                    layers::backward(
+                        &params,
+                        &layout,
+                        &input,
+                        &forward,
+                        targets[i],
+                        &mut gradient,
+                    );
+                }
+                let step = self.learning_rate / batch.len() as f32;
+                for ((p, v), g) in params.iter_mut().zip(&mut velocity).zip(&gradient) {
+                    *v = MOMENTUM * *v - step * g;
+                    *p += *v;
+                }
+            }
+        }
+        Ok(CnnModel::from_parts(labels, params))
+    }
+}
+
+impl Default for CnnTrainer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Cut the input window at offset (`dx`, `dy`) out of a sample.
+fn cut(sample: &[f32], dx: usize, dy: usize, input: &mut [f32]) {
+    for (y, row) in input.chunks_mut(INPUT_WIDTH).enumerate() {
+        let start = (y + dy) * SAMPLE_WIDTH + dx;
+        row.copy_from_slice(&sample[start..start + INPUT_WIDTH]);
+    }
+}
+
+/// xorshift64*, enough for shuffling and initialization.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// uniform in `[0, 1)`.
+    fn unit(&mut self) -> f32 {
+        (self.next() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            items.swap(i, self.below(i + 1));
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+///
+///  The error kind used by `CaptchaError`
+///
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum CaptchaErrorKind {
+    /// The image file could not be decoded.
+    Decode,
+    /// The image is too small or too large to hold a validate code.
+    InvalidSize,
+    /// The image format is recognized, but decoding it is not enabled.
+    UnsupportedFormat,
+    /// The digits found in the image do not look like a validate code.
+    Segmentation,
+    /// A template file could not be read or is malformed.
+    Templates,
+    /// A model file could not be read or is malformed, or a model could not be trained.
+    Model,
+    /// The characters of an arithmetic code do not make an expression which can be
+    /// evaluated.
+    Expression,
+}
+
+///
+/// The error type of captcha recognition.
+///
+/// use `kind()` method to get `CaptchaErrorKind`, and `source()` method to get the
+/// underlying error if there is one.
+///
+#[derive(Debug)]
+pub struct CaptchaError {
+    kind: CaptchaErrorKind,
+    inner: Option<Box<dyn Error + Send + Sync + 'static>>,
+}
+
+impl CaptchaError {
+    pub(crate) fn with_source<E>(kind: CaptchaErrorKind, inner: E) -> Self
+    where
+        E: Into<Box<dyn Error + Send + Sync + 'static>>,
+    {
+        Self {
+            kind,
+            inner: Some(inner.into()),
+        }
+    }
+
+    pub fn kind(&self) -> CaptchaErrorKind {
+        self.kind
+    }
+}
+
+impl Display for CaptchaErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        use CaptchaErrorKind::*;
+        match self {
+            Decode => {
+                write!(f, "Captcha image can not be decoded")
+            }
+            InvalidSize => {
+                write!(f, "Captcha image size invalid")
+            }
+            UnsupportedFormat => {
+                write!(f, "Captcha image format unsupported")
+            }
+            Segmentation => {
+                write!(f, "Captcha digits can not be segmented")
+            }
+            Templates => {
+                write!(f, "Captcha templates can not be loaded")
+            }
+            Model => {
+                write!(f, "Captcha model can not be loaded")
+            }
+            Expression => {
+                write!(f, "Captcha expression can not be evaluated")
+            }
+        }
+    }
+}
+
+impl Display for CaptchaError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.inner {
+            Some(inner) => write!(f, "{}: {inner}", self.kind),
+            None => self.kind.fmt(f),
+        }
+    }
+}
+
+impl Error for CaptchaError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.inner
+            .as_ref()
+            .map(|e| e.as_ref() as &(dyn Error + 'static))
+    }
+}
@@ -0,0 +1,145 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+
+/// stands for a digit that was not recognized at all.
+const MISSING: char = '?';
+
+///
+/// Accuracy statistics of a recognizer over labeled samples.
+///
+/// Feed every sample to [`Evaluation::add`] with the expected code and what was
+/// recognized. The [`Display`] output is a report with the accuracy of whole codes and
+/// of single digits, and a confusion matrix whose rows are the expected digits and
+/// columns the recognized ones, `?` for digits of codes that could not be recognized.
+///
+/// The `ustc-cas-captcha-eval` binary, enabled by `eval` feature, prints this report for
+/// a directory of images.
+///
+/// # Example
+/// ```rust
+/// use ustc_cas::captcha::{self, Evaluation};
+///
+/// # fn run(samples: Vec<(String, Vec<u8>)>) {
+/// let mut evaluation = Evaluation::new();
+/// for (code, image) in &samples {
+///     let recognized = captcha::recognize(image).ok().map(|r| r.code());
+///     evaluation.add(code, recognized.as_deref());
+/// }
+/// println!("{evaluation}");
+/// assert!(evaluation.code_accuracy() > 0.9);
+/// # }
+/// ```
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Evaluation {
+    samples: usize,
+    correct: usize,
+    confusion: BTreeMap<(char, char), usize>,
+}
+
+impl Evaluation {
+    /// Create an empty evaluation.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a sample showing `expected`, recognized as `recognized`, or `None` if
+    /// recognition failed. If the lengths differ, every digit counts as not recognized.
+    pub fn add(&mut self, expected: &str, recognized: Option<&str>) {
+        self.samples += 1;
+        if recognized == Some(expected) {
+            self.correct += 1;
+        }
+
+        let recognized = recognized
+            .filter(|r| r.chars().count() == expected.chars().count())
+            .unwrap_or("");
+        let mut recognized = recognized.chars();
+        for e in expected.chars() {
+            let r = recognized.next().unwrap_or(MISSING);
+            *self.confusion.entry((e, r)).or_default() += 1;
+        }
+    }
+
+    /// The number of samples.
+    pub fn samples(&self) -> usize {
+        self.samples
+    }
+
+    /// Fraction of codes recognized entirely right, `0.0` without samples.
+    pub fn code_accuracy(&self) -> f64 {
+        ratio(self.correct, self.samples)
+    }
+
+    /// Fraction of digits recognized right, `0.0` without samples.
+    pub fn digit_accuracy(&self) -> f64 {
+        let total = self.confusion.values().sum();
+        let right = self
+            .confusion
+            .iter()
+            .filter(|((e, r), _)| e == r)
+            .map(|(_, n)| n)
+            .sum();
+        ratio(right, total)
+    }
+
+    /// Fraction of the occurrences of `digit` recognized right, `None` if `digit` was
+    /// never expected.
+    pub fn accuracy_of(&self, digit: char) -> Option<f64> {
+        let total: usize = self
+            .confusion
+            .iter()
+            .filter(|((e, _), _)| *e == digit)
+            .map(|(_, n)| n)
+            .sum();
+        (total > 0).then(|| ratio(self.confused(digit, digit), total))
+    }
+
+    /// How many times `expected` was recognized as `recognized`. Pass `'?'` as
+    /// `recognized` for the digits of codes which could not be recognized.
+    pub fn confused(&self, expected: char, recognized: char) -> usize {
+        self.confusion
+            .get(&(expected, recognized))
+            .copied()
+            .unwrap_or(0)
+    }
+}
+
+fn ratio(n: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        n as f64 / total as f64
+    }
+}
+
+impl Display for Evaluation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "samples: {}", self.samples)?;
+        writeln!(f, "code accuracy: {:.2}%", self.code_accuracy() * 100.0)?;
+        writeln!(f, "digit accuracy: {:.2}%", self.digit_accuracy() * 100.0)?;
+
+        let expected: BTreeSet<char> = self.confusion.keys().map(|k| k.0).collect();
+        let recognized: BTreeSet<char> = self.confusion.keys().map(|k| k.1).collect();
+        let columns: Vec<char> = expected.union(&recognized).copied().collect();
+
+        writeln!(f)?;
+        write!(f, "     ")?;
+        for c in &columns {
+            write!(f, "{c:>5}")?;
+        }
+        writeln!(f, "  accuracy")?;
+        for &e in &expected {
+            write!(f, "{e:>5}")?;
+            for &r in &columns {
+                match self.confused(e, r) {
+                    0 => write!(f, "{:>5}", ".")?,
+                    n => write!(f, "{n:>5}")?,
+                }
+            }
+            let accuracy = self.accuracy_of(e).unwrap_or(0.0);
+            writeln!(f, "  {:>7.2}%", accuracy * 100.0)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,504 @@
+use super::luma::MAX_SIDE;
+use super::{CaptchaError, CaptchaErrorKind, LumaImage};
+
+/// position in a block of the `k`th coefficient in zigzag order.
+const ZIGZAG: [usize; 64] = [
+    0, 1, 8, 16, 9, 2, 3, 10, 17, 24, 32, 25, 18, 11, 4, 5, 12, 19, 26, 33, 40, 48, 41, 34, 27, 20,
+    13, 6, 7, 14, 21, 28, 35, 42, 49, 56, 57, 50, 43, 36, 29, 22, 15, 23, 30, 37, 44, 51, 58, 59,
+    52, 45, 38, 31, 39, 46, 53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+fn error(message: &str) -> CaptchaError {
+    CaptchaError::with_source(CaptchaErrorKind::Decode, format!("JPEG {message}"))
+}
+
+///
+/// Decode the luma of a baseline JPEG.
+///
+/// Only the first component is decoded, which is the luma of grayscale and YCbCr images.
+/// The others are parsed and dropped. Progressive, lossless and arithmetic coded images
+/// are not supported.
+///
+pub(crate) fn decode(data: &[u8]) -> Result<LumaImage, CaptchaError> {
+    if !data.starts_with(&[0xff, 0xd8]) {
+        return Err(error("start of image missing"));
+    }
+    let mut decoder = Decoder {
+        quantization: [[0; 64]; 4],
+        huffman: [None, None, None, None, None, None, None, None],
+        restart_interval: 0,
+        frame: None,
+    };
+
+    let mut pos = 2;
+    loop {
+        // markers may be preceded by any number of fill bytes
+        while data.get(pos) == Some(&0xff) && data.get(pos + 1) == Some(&0xff) {
+            pos += 1;
+        }
+        let marker = match data.get(pos..pos + 2) {
+            Some(&[0xff, marker]) => marker,
+            _ => return Err(error("end of image missing, data truncated")),
+        };
+        pos += 2;
+        if marker == 0xd9 {
+            break;
+        }
+        let length = match data.get(pos..pos + 2) {
+            Some(&[high, low]) => u16::from_be_bytes([high, low]) as usize,
+            _ => return Err(error("segment truncated")),
+        };
+        let segment = data
+            .get(pos + 2..pos + length)
+            .filter(|_| length >= 2)
+            .ok_or_else(|| error("segment truncated"))?;
+        pos += length;
+
+        match marker {
+            0xc0 | 0xc1 => decoder.read_frame(segment)?,
+            0xc2 | 0xc3 | 0xc5..=0xc7 | 0xc9..=0xcb | 0xcd..=0xcf => {
+                return Err(error("coding process not supported, only baseline is"))
+            }
+            0xc4 => decoder.read_huffman(segment)?,
+            0xdb => decoder.read_quantization(segment)?,
+            0xdd => {
+                decoder.restart_interval = match segment {
+                    &[high, low] => u16::from_be_bytes([high, low]),
+                    _ => return Err(error("restart interval malformed")),
+                }
+            }
+            0xda => {
+                pos += decoder.read_scan(segment, &data[pos..])?;
+                // skip to the next marker, past any leftover restart markers
+                while pos < data.len() {
+                    if data[pos] == 0xff
+                        && data
+                            .get(pos + 1)
+                            .map_or(false, |&m| m != 0 && !(0xd0..=0xd7).contains(&m))
+                    {
+                        break;
+                    }
+                    pos += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let frame = decoder.frame.ok_or_else(|| error("frame missing"))?;
+    let stride = frame.plane_width;
+    let pixels = frame
+        .plane
+        .chunks(stride)
+        .take(frame.height)
+        .flat_map(|row| &row[..frame.width])
+        .copied()
+        .collect();
+    Ok(LumaImage::from_raw(frame.width as u32, frame.height as u32, pixels).unwrap())
+}
+
+struct Component {
+    id: u8,
+    h: usize,
+    v: usize,
+    quantization: usize,
+}
+
+struct Frame {
+    width: usize,
+    height: usize,
+    components: Vec<Component>,
+    h_max: usize,
+    v_max: usize,
+    mcus_x: usize,
+    mcus_y: usize,
+    /// samples of the first component, whole blocks of every MCU.
+    plane: Vec<u8>,
+    plane_width: usize,
+}
+
+struct Decoder {
+    quantization: [[u16; 64]; 4],
+    /// DC tables 0 to 3, then AC tables 0 to 3.
+    huffman: [Option<Huffman>; 8],
+    restart_interval: u16,
+    frame: Option<Frame>,
+}
+
+impl Decoder {
+    fn read_quantization(&mut self, mut segment: &[u8]) -> Result<(), CaptchaError> {
+        while let Some((&pq_tq, rest)) = segment.split_first() {
+            let (sixteen, table) = (pq_tq >> 4, (pq_tq & 15) as usize);
+            let size = if sixteen == 1 { 128 } else { 64 };
+            if sixteen > 1 || table > 3 || rest.len() < size {
+                return Err(error("quantization table malformed"));
+            }
+            for k in 0..64 {
+                self.quantization[table][k] = if sixteen == 1 {
+                    u16::from_be_bytes([rest[k * 2], rest[k * 2 + 1]])
+                } else {
+                    rest[k] as u16
+                };
+            }
+            segment = &rest[size..];
+        }
+        Ok(())
+    }
+
+    fn read_huffman(&mut self, mut segment: &[u8]) -> Result<(), CaptchaError> {
+        while segment.len() >= 17 {
+            let (class, table) = ((segment[0] >> 4) as usize, (segment[0] & 15) as usize);
+            if class > 1 || table > 3 {
+                return Err(error("huffman table malformed"));
+            }
+            let mut counts = [0; 16];
+            counts.copy_from_slice(&segment[1..17]);
+            let total: usize = counts.iter().map(|&c| c as usize).sum();
+            let values = segment
+                .get(17..17 + total)
+                .ok_or_else(|| error("huffman table malformed"))?;
+            // DC values are categories of at most 11 bits, AC values have a run in the
+            // high nibble and a category of at most 10 bits in the low one
+            let category_too_large = if class == 0 {
+                values.iter().any(|&v| v > 11)
+            } else {
+                values.iter().any(|&v| v & 15 > 10)
+            };
+            if category_too_large {
+                return Err(error("huffman table malformed, category too large"));
+            }
+            self.huffman[class * 4 + table] = Some(Huffman::new(counts, values.to_vec()));
+            segment = &segment[17 + total..];
+        }
+        if segment.is_empty() {
+            Ok(())
+        } else {
+            Err(error("huffman table malformed"))
+        }
+    }
+
+    fn read_frame(&mut self, segment: &[u8]) -> Result<(), CaptchaError> {
+        if self.frame.is_some() {
+            return Err(error("has more than one frame"));
+        }
+        let (precision, height, width, count) = match segment {
+            &[p, h1, h0, w1, w0, n, ..] => (
+                p,
+                u16::from_be_bytes([h1, h0]) as usize,
+                u16::from_be_bytes([w1, w0]) as usize,
+                n as usize,
+            ),
+            _ => return Err(error("frame header malformed")),
+        };
+        if precision != 8 {
+            return Err(error("sample precision not supported"));
+        }
+        if width == 0 || height == 0 {
+            return Err(error("size missing"));
+        }
+        if width > MAX_SIDE as usize || height > MAX_SIDE as usize {
+            return Err(CaptchaError::with_source(
+                CaptchaErrorKind::InvalidSize,
+                format!("image of {width}x{height} is too large"),
+            ));
+        }
+        let specs = segment
+            .get(6..6 + count * 3)
+            .filter(|_| count > 0)
+            .ok_or_else(|| error("frame header malformed"))?;
+        let components: Vec<_> = specs
+            .chunks(3)
+            .map(|c| Component {
+                id: c[0],
+                h: (c[1] >> 4) as usize,
+                v: (c[1] & 15) as usize,
+                quantization: c[2] as usize,
+            })
+            .collect();
+        if components
+            .iter()
+            .any(|c| !(1..=4).contains(&c.h) || !(1..=4).contains(&c.v) || c.quantization > 3)
+        {
+            return Err(error("frame header malformed"));
+        }
+
+        let h_max = components.iter().map(|c| c.h).max().unwrap();
+        let v_max = components.iter().map(|c| c.v).max().unwrap();
+        if components[0].h != h_max || components[0].v != v_max {
+            return Err(error("subsampled luma not supported"));
+        }
+        let mcus_x = (width + 8 * h_max - 1) / (8 * h_max);
+        let mcus_y = (height + 8 * v_max - 1) / (8 * v_max);
+        let plane_width = mcus_x * h_max * 8;
+        let plane_height = mcus_y * v_max * 8;
+        self.frame = Some(Frame {
+            width,
+            height,
+            components,
+            h_max,
+            v_max,
+            mcus_x,
+            mcus_y,
+            plane: vec![0; plane_width * plane_height],
+            plane_width,
+        });
+        Ok(())
+    }
+
+    /// Decode the scan following the header `segment`, and return the length of the
+    /// entropy coded data.
+    fn read_scan(&mut self, segment: &[u8], data: &[u8]) -> Result<usize, CaptchaError> {
+        let frame = self
+            .frame
+            .as_mut()
+            .ok_or_else(|| error("scan before frame"))?;
+        let count = *segment
+            .first()
+            .ok_or_else(|| error("scan header malformed"))? as usize;
+        let specs = segment
+            .get(1..1 + count * 2)
+            .filter(|_| (1..=4).contains(&count) && segment.len() == 4 + count * 2)
+            .ok_or_else(|| error("scan header malformed"))?;
+
+        // (index in frame, DC table, AC table) of every component of the scan
+        let mut scan = vec![];
+        for spec in specs.chunks(2) {
+            let index = frame
+                .components
+                .iter()
+                .position(|c| c.id == spec[0])
+                .ok_or_else(|| error("scan of unknown component"))?;
+            let (dc, ac) = ((spec[1] >> 4) as usize, (spec[1] & 15) as usize);
+            if dc > 3 || ac > 3 || self.huffman[dc].is_none() || self.huffman[4 + ac].is_none() {
+                return Err(error("huffman table missing"));
+            }
+            scan.push((index, dc, 4 + ac));
+        }
+
+        // blocks of every MCU, as (scan component, block column, block row)
+        let (mcus_x, mcus_y, blocks) = if let [(index, _, _)] = scan[..] {
+            // a single component is not interleaved, every block is an MCU
+            let c = &frame.components[index];
+            let width = (frame.width * c.h + frame.h_max - 1) / frame.h_max;
+            let height = (frame.height * c.v + frame.v_max - 1) / frame.v_max;
+            ((width + 7) / 8, (height + 7) / 8, vec![(0, 0, 0)])
+        } else {
+            let mut blocks = vec![];
+            for (i, &(index, _, _)) in scan.iter().enumerate() {
+                let c = &frame.components[index];
+                for y in 0..c.v {
+                    for x in 0..c.h {
+                        blocks.push((i, x, y));
+                    }
+                }
+            }
+            (frame.mcus_x, frame.mcus_y, blocks)
+        };
+
+        let mut reader = BitReader::new(data);
+        let mut predictions = vec![0; scan.len()];
+        let mut samples = [0u8; 64];
+        for mcu in 0..mcus_x * mcus_y {
+            if self.restart_interval > 0 && mcu > 0 && mcu % self.restart_interval as usize == 0 {
+                reader.restart();
+                predictions.iter_mut().for_each(|p| *p = 0);
+            }
+            let (mx, my) = (mcu % mcus_x, mcu / mcus_x);
+            for &(i, bx, by) in &blocks {
+                let (index, dc, ac) = scan[i];
+                let component = &frame.components[index];
+                let quantization = &self.quantization[component.quantization];
+                let mut coefficients = [0; 64];
+                let dc = self.huffman[dc].as_ref().unwrap();
+                let ac = self.huffman[ac].as_ref().unwrap();
+                decode_block(&mut reader, dc, ac, &mut predictions[i], &mut coefficients)?;
+                if index != 0 {
+                    continue;
+                }
+
+                let mut block = [0; 64];
+                for k in 0..64 {
+                    block[ZIGZAG[k]] = coefficients[k].wrapping_mul(quantization[k] as i32);
+                }
+                idct(&block, &mut samples);
+                let (x, y) = if blocks.len() == 1 {
+                    (mx, my)
+                } else {
+                    (mx * component.h + bx, my * component.v + by)
+                };
+                for row in 0..8 {
+                    let start = (y * 8 + row) * frame.plane_width + x * 8;
+                    if let Some(out) = frame.plane.get_mut(start..start + 8) {
+                        out.copy_from_slice(&samples[row * 8..row * 8 + 8]);
+                    }
+                }
+            }
+        }
+        Ok(reader.pos)
+    }
+}
+
+fn decode_block(
+    reader: &mut BitReader<'_>,
+    dc: &Huffman,
+    ac: &Huffman,
+    prediction: &mut i32,
+    coefficients: &mut [i32; 64],
+) -> Result<(), CaptchaError> {
+    let size = dc.decode(reader)?;
+    // a corrupt image may drift the prediction out of range, which only garbles it
+    *prediction = prediction.wrapping_add(extend(reader.bits(size), size));
+    coefficients[0] = *prediction;
+
+    let mut k = 1;
+    while k < 64 {
+        let rs = ac.decode(reader)?;
+        let (run, size) = (rs >> 4, rs & 15);
+        if size == 0 {
+            if run != 15 {
+                break;
+            }
+            k += 16;
+            continue;
+        }
+        k += run as usize;
+        if k > 63 {
+            return Err(error("coefficient out of block"));
+        }
+        coefficients[k] = extend(reader.bits(size), size);
+        k += 1;
+    }
+    Ok(())
+}
+
+/// The signed value of the `size` bits `value`.
+fn extend(value: u32, size: u8) -> i32 {
+    if size == 0 {
+        0
+    } else if value < 1 << (size - 1) {
+        value as i32 - (1 << size) + 1
+    } else {
+        value as i32
+    }
+}
+
+/// Inverse DCT of a block of dequantized coefficients in natural order.
+fn idct(coefficients: &[i32; 64], samples: &mut [u8; 64]) {
+    // COS[x][u] = c(u) * cos((2x + 1) * u * pi / 16) / 2
+    let mut cos = [[0f32; 8]; 8];
+    for (x, row) in cos.iter_mut().enumerate() {
+        for (u, c) in row.iter_mut().enumerate() {
+            let scale = if u == 0 {
+                std::f32::consts::FRAC_1_SQRT_2
+            } else {
+                1.0
+            };
+            *c = scale * ((2 * x + 1) as f32 * u as f32 * std::f32::consts::PI / 16.0).cos() / 2.0;
+        }
+    }
+
+    let mut rows = [0f32; 64];
+    for v in 0..8 {
+        for x in 0..8 {
+            rows[v * 8 + x] = (0..8)
+                .map(|u| cos[x][u] * coefficients[v * 8 + u] as f32)
+                .sum();
+        }
+    }
+    for y in 0..8 {
+        for x in 0..8 {
+            let value: f32 = (0..8).map(|v| cos[y][v] * rows[v * 8 + x]).sum();
+            samples[y * 8 + x] = (value + 128.0).round().clamp(0.0, 255.0) as u8;
+        }
+    }
+}
+
+struct Huffman {
+    /// the first code of every length.
+    first: [u32; 16],
+    counts: [u8; 16],
+    /// index in `values` of the first code of every length.
+    offsets: [usize; 16],
+    values: Vec<u8>,
+}
+
+impl Huffman {
+    fn new(counts: [u8; 16], values: Vec<u8>) -> Self {
+        let mut first = [0; 16];
+        let mut offsets = [0; 16];
+        let (mut code, mut offset) = (0, 0);
+        for length in 0..16 {
+            first[length] = code;
+            offsets[length] = offset;
+            code = (code + counts[length] as u32) << 1;
+            offset += counts[length] as usize;
+        }
+        Self {
+            first,
+            counts,
+            offsets,
+            values,
+        }
+    }
+
+    fn decode(&self, reader: &mut BitReader<'_>) -> Result<u8, CaptchaError> {
+        let mut code = 0;
+        for length in 0..16 {
+            code = (code << 1) | reader.bits(1);
+            let index = code.wrapping_sub(self.first[length]);
+            if index < self.counts[length] as u32 {
+                return Ok(self.values[self.offsets[length] + index as usize]);
+            }
+        }
+        Err(error("huffman code invalid"))
+    }
+}
+
+/// Reads the entropy coded data of a scan, removing stuffed bytes. Markers stop the
+/// reader, which then yields zeros, like truncated data does.
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    buffer: u32,
+    count: u8,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            pos: 0,
+            buffer: 0,
+            count: 0,
+        }
+    }
+
+    fn bits(&mut self, n: u8) -> u32 {
+        while self.count < n {
+            let byte = match self.data.get(self.pos..) {
+                Some(&[0xff, 0, ..]) => {
+                    self.pos += 2;
+                    0xff
+                }
+                Some(&[0xff, ..]) | Some(&[]) | None => 0,
+                Some(&[byte, ..]) => {
+                    self.pos += 1;
+                    byte
+                }
+            };
+            self.buffer = (self.buffer << 8) | byte as u32;
+            self.count += 8;
+        }
+        self.count -= n;
+        (self.buffer >> self.count) & ((1 << n) - 1)
+    }
+
+    /// Drop the bits left and skip the restart marker.
+    fn restart(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+        if let Some(&[0xff, 0xd0..=0xd7, ..]) = self.data.get(self.pos..) {
+            self.pos += 2;
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use super::segment::Segment;
+use super::template::{Glyph, Templates, THRESHOLD};
+use super::{Alphabet, LumaImage};
+
+/// how far, in pixels, a digit may be off its expected position.
+const JITTER: i32 = 2;
+
+/// The classification of a digit.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct DigitMatch {
+    pub value: char,
+    /// fraction of known pixels agreeing with the nearest template of `value`.
+    pub score: f32,
+    /// margin of `score` over the nearest template of any other digit.
+    pub confidence: f32,
+}
+
+///
+/// Classify the digit in `segment` by a vote among the `k` nearest templates.
+///
+/// Each template is aligned with the top left corner of the segment, then searched
+/// within `JITTER` pixels around it, and its distance is the Hamming distance at the best
+/// position. Every one of the `k` nearest templates votes for its digit, ties going to
+/// the digit with the nearest template. Templates of characters outside `alphabet` are
+/// left out.
+///
+/// The confidence is `0.0` when the vote disagrees with the single nearest template.
+///
+pub(crate) fn classify(
+    image: &LumaImage,
+    segment: &Segment,
+    templates: &Templates,
+    k: usize,
+    alphabet: Option<&Alphabet>,
+) -> DigitMatch {
+    let mut neighbors: Vec<(char, f32)> = templates
+        .iter()
+        .filter(|template| alphabet.map_or(true, |a| a.contains(template.value)))
+        .map(|template| {
+            let (x, y) = (
+                segment.x as i32 - template.left,
+                segment.y as i32 - template.top,
+            );
+            let score = offsets()
+                .map(|(dx, dy)| {
+                    template.score(&Glyph::from_window(image, x + dx, y + dy, THRESHOLD))
+                })
+                .fold(0.0, f32::max);
+            (template.value, score)
+        })
+        .collect();
+    neighbors.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+
+    // votes and nearest score of every digit among the k nearest, nearest first
+    let mut votes: Vec<(char, usize, f32)> = vec![];
+    for &(value, score) in neighbors.iter().take(k.max(1)) {
+        match votes.iter_mut().find(|v| v.0 == value) {
+            Some(v) => v.1 += 1,
+            None => votes.push((value, 1, score)),
+        }
+    }
+    let (value, _, score) =
+        votes
+            .iter()
+            .copied()
+            .fold(('?', 0, 0.0), |best, v| if v.1 > best.1 { v } else { best });
+
+    let second = neighbors.iter().find(|n| n.0 != value).map_or(0.0, |n| n.1);
+    DigitMatch {
+        value,
+        score,
+        confidence: (score - second).max(0.0),
+    }
+}
+
+/// offsets within `JITTER`, nearest first.
+fn offsets() -> impl Iterator<Item = (i32, i32)> {
+    let mut offsets: Vec<_> = (-JITTER..=JITTER)
+        .flat_map(|dy| (-JITTER..=JITTER).map(move |dx| (dx, dy)))
+        .collect();
+    offsets.sort_by_key(|(dx, dy)| dx.abs() + dy.abs());
+    offsets.into_iter()
+}
@@ -0,0 +1,268 @@
+use super::jpeg;
+use super::template::GLYPH_WIDTH;
+use super::{CaptchaError, CaptchaErrorKind};
+#[cfg(feature = "validate-code")]
+use image::{
+    io::{Limits, Reader as ImageReader},
+    ImageError,
+};
+use std::borrow::Cow;
+#[cfg(feature = "validate-code")]
+use std::io::Cursor;
+
+/// the largest width and height accepted, validate codes are far smaller.
+pub(crate) const MAX_SIDE: u32 = 2048;
+
+/// height of the validate codes served by CAS, which the templates are drawn at.
+const REFERENCE_HEIGHT: u32 = 30;
+
+///
+/// The image file formats a validate code may come in.
+///
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ImageFormat {
+    Jpeg,
+    /// Decoding requires enabling `png` feature.
+    Png,
+    /// Decoding requires enabling `gif` feature. Only the first frame is used.
+    Gif,
+}
+
+impl ImageFormat {
+    /// Detect the format of an image file from its first bytes.
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        if data.starts_with(&[0xff, 0xd8, 0xff]) {
+            Some(ImageFormat::Jpeg)
+        } else if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(ImageFormat::Png)
+        } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+            Some(ImageFormat::Gif)
+        } else {
+            None
+        }
+    }
+
+    /// Whether images of this format can be decoded with the enabled features.
+    pub fn is_supported(self) -> bool {
+        match self {
+            ImageFormat::Jpeg => true,
+            ImageFormat::Png => cfg!(feature = "png"),
+            ImageFormat::Gif => cfg!(feature = "gif"),
+        }
+    }
+
+    fn feature(self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "validate-code-lite",
+            ImageFormat::Png => "png",
+            ImageFormat::Gif => "gif",
+        }
+    }
+}
+
+#[cfg(feature = "validate-code")]
+impl From<ImageFormat> for image::ImageFormat {
+    fn from(format: ImageFormat) -> Self {
+        match format {
+            ImageFormat::Jpeg => image::ImageFormat::Jpeg,
+            ImageFormat::Png => image::ImageFormat::Png,
+            ImageFormat::Gif => image::ImageFormat::Gif,
+        }
+    }
+}
+
+///
+/// An 8-bit grayscale image, the form every captcha image is turned into before
+/// recognition.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LumaImage {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl LumaImage {
+    /// Create an image from row-major luma values.
+    ///
+    /// Returns `None` if `pixels` does not hold exactly `width * height` values.
+    pub fn from_raw(width: u32, height: u32, pixels: Vec<u8>) -> Option<Self> {
+        (pixels.len() == width as usize * height as usize).then(|| Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    /// Decode an image file, such as the JPEG served by `validatecode.jsp`.
+    ///
+    /// The format is detected from the content, see [`ImageFormat`]. Returns an error of
+    /// kind [`UnsupportedFormat`](CaptchaErrorKind::UnsupportedFormat) if decoding the
+    /// format is not enabled, or of kind [`InvalidSize`](CaptchaErrorKind::InvalidSize)
+    /// for images wider or taller than 2048 pixels.
+    ///
+    /// Images are decoded by `image` crate. With `validate-code-lite` feature alone, JPEGs
+    /// are decoded by [`LumaImage::decode_jpeg`] instead.
+    pub fn decode(data: &[u8]) -> Result<Self, CaptchaError> {
+        let format = ImageFormat::detect(data).ok_or_else(|| {
+            CaptchaError::with_source(CaptchaErrorKind::Decode, "unknown image format")
+        })?;
+        if !format.is_supported() {
+            return Err(CaptchaError::with_source(
+                CaptchaErrorKind::UnsupportedFormat,
+                format!(
+                    "decoding {format:?} requires `{}` feature",
+                    format.feature()
+                ),
+            ));
+        }
+
+        #[cfg(not(feature = "validate-code"))]
+        return Self::decode_jpeg(data);
+
+        #[cfg(feature = "validate-code")]
+        Self::decode_image(data, format)
+    }
+
+    /// Decode a baseline JPEG with the decoder built into this crate, which needs no
+    /// `image` crate.
+    ///
+    /// Only the luma of the image is decoded, so the pixels of colored images differ a
+    /// little from those of [`LumaImage::decode`] with `image` crate, which converts
+    /// colors to luma with other weights. Progressive JPEGs are not supported.
+    pub fn decode_jpeg(data: &[u8]) -> Result<Self, CaptchaError> {
+        jpeg::decode(data)
+    }
+
+    #[cfg(feature = "validate-code")]
+    fn decode_image(data: &[u8], format: ImageFormat) -> Result<Self, CaptchaError> {
+        let mut limits = Limits::default();
+        limits.max_image_width = Some(MAX_SIDE);
+        limits.max_image_height = Some(MAX_SIDE);
+        let mut reader = ImageReader::with_format(Cursor::new(data), format.into());
+        reader.limits(limits);
+        let img = reader
+            .decode()
+            .map_err(|e| match e {
+                ImageError::Limits(_) => {
+                    CaptchaError::with_source(CaptchaErrorKind::InvalidSize, e)
+                }
+                e => CaptchaError::with_source(CaptchaErrorKind::Decode, e),
+            })?
+            .into_luma8();
+        let (width, height) = img.dimensions();
+        Ok(Self {
+            width,
+            height,
+            pixels: img.into_raw(),
+        })
+    }
+
+    /// Check the size of the image and scale it to the height of the validate codes
+    /// served by CAS, keeping the aspect ratio.
+    pub(crate) fn normalize(&self) -> Result<Cow<'_, Self>, CaptchaError> {
+        let (width, height) = (self.width, self.height);
+        if width > MAX_SIDE || height > MAX_SIDE {
+            return Err(CaptchaError::with_source(
+                CaptchaErrorKind::InvalidSize,
+                format!("image of {width}x{height} is too large"),
+            ));
+        }
+        if height < REFERENCE_HEIGHT / 2 || width < GLYPH_WIDTH {
+            return Err(CaptchaError::with_source(
+                CaptchaErrorKind::InvalidSize,
+                format!("image of {width}x{height} is too small"),
+            ));
+        }
+        if height == REFERENCE_HEIGHT {
+            return Ok(Cow::Borrowed(self));
+        }
+
+        let scaled = (width * REFERENCE_HEIGHT + height / 2) / height;
+        if scaled < GLYPH_WIDTH {
+            return Err(CaptchaError::with_source(
+                CaptchaErrorKind::InvalidSize,
+                format!("image of {width}x{height} is too narrow"),
+            ));
+        }
+        Ok(Cow::Owned(self.resize(scaled, REFERENCE_HEIGHT)))
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The luma values, row by row.
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    /// The luma at (`x`, `y`).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the position is out of the image.
+    pub fn get(&self, x: u32, y: u32) -> u8 {
+        assert!(x < self.width && y < self.height, "pixel out of image");
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    /// Copy a rectangle out of the image. The rectangle is clipped to the image.
+    pub fn crop(&self, x: u32, y: u32, width: u32, height: u32) -> Self {
+        let x = x.min(self.width);
+        let y = y.min(self.height);
+        let width = width.min(self.width - x);
+        let height = height.min(self.height - y);
+        let mut pixels = Vec::with_capacity((width * height) as usize);
+        for row in y..y + height {
+            let start = (row * self.width + x) as usize;
+            pixels.extend_from_slice(&self.pixels[start..start + width as usize]);
+        }
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Scale the image to `width` x `height`, averaging the pixels covered by each
+    /// new pixel.
+    pub fn resize(&self, width: u32, height: u32) -> Self {
+        let width = width.max(1);
+        let height = height.max(1);
+        if self.width == 0 || self.height == 0 {
+            return Self {
+                width,
+                height,
+                pixels: vec![255; (width * height) as usize],
+            };
+        }
+
+        let mut pixels = Vec::with_capacity((width * height) as usize);
+        for y in 0..height {
+            let y0 = y * self.height / height;
+            let y1 = ((y + 1) * self.height / height).max(y0 + 1);
+            for x in 0..width {
+                let x0 = x * self.width / width;
+                let x1 = ((x + 1) * self.width / width).max(x0 + 1);
+                let mut sum = 0u32;
+                for sy in y0..y1 {
+                    for sx in x0..x1 {
+                        sum += self.get(sx, sy) as u32;
+                    }
+                }
+                pixels.push((sum / ((y1 - y0) * (x1 - x0))) as u8);
+            }
+        }
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+}
@@ -0,0 +1,348 @@
+//! recognition of the validate code (captcha) of USTC CAS.
+//!
+//! The recognizer is what [`TemplateSolver`](crate::TemplateSolver) uses during login,
+//! and it can be used on its own for any image served by
+//! `https://passport.ustc.edu.cn/validatecode.jsp`.
+//!
+//! Using this module requires enabling `validate-code` or `validate-code-lite` feature.
+//!
+//! # Example
+//! ```rust
+//! use ustc_cas::captcha;
+//!
+//! # fn run(image: &[u8]) -> Result<(), captcha::CaptchaError> {
+//! let recognition = captcha::recognize(image)?;
+//! println!("code: {}", recognition.code());
+//! for digit in recognition.digits() {
+//!     println!("{}: confidence {:.2}", digit.value, digit.confidence);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+mod alphabet;
+mod arithmetic;
+#[cfg(feature = "cnn")]
+mod cnn;
+mod error;
+mod eval;
+mod jpeg;
+mod knn;
+mod luma;
+mod preprocess;
+mod segment;
+mod template;
+mod train;
+
+pub use alphabet::Alphabet;
+#[cfg(feature = "cnn")]
+pub use cnn::{CnnModel, CnnTrainer};
+pub use error::*;
+pub use eval::Evaluation;
+pub use luma::*;
+pub use preprocess::*;
+pub use template::Templates;
+pub use train::TemplateTrainer;
+
+use arithmetic::evaluate;
+use knn::classify;
+use segment::segment;
+use template::THRESHOLD;
+
+///
+/// Recognize the validate code in an image file with the default [`Recognizer`].
+///
+pub fn recognize(image: &[u8]) -> Result<Recognition, CaptchaError> {
+    Recognizer::default().recognize(image)
+}
+
+///
+/// Recognize the validate code in an already decoded image with the default
+/// [`Recognizer`].
+///
+pub fn recognize_luma(image: &LumaImage) -> Result<Recognition, CaptchaError> {
+    Recognizer::default().recognize_luma(image)
+}
+
+///
+/// A configurable validate code recognizer.
+///
+/// The image is first cleaned by a preprocessing [`Pipeline`], then the digits are
+/// located from the ink in the image rather than from fixed coordinates, so codes of any
+/// length from one to eight digits are recognized. An error of kind
+/// [`CaptchaErrorKind::Segmentation`] is returned if the digits found do not look like
+/// a validate code, such as when a digit is cut off by the image border.
+///
+/// Images of other sizes than the 130x30 served by CAS are scaled to a height of 30
+/// pixels first, keeping the aspect ratio. An error of kind
+/// [`CaptchaErrorKind::InvalidSize`] is returned for images lower than 15 pixels or
+/// larger than 2048 pixels.
+///
+/// Each digit is classified by a vote among the nearest [`Templates`], so a template set
+/// may hold several templates for a digit drawn in different ways, see
+/// [`Recognizer::neighbors`]. With `cnn` feature, a convolutional network may classify
+/// the digits instead, see `Recognizer::cnn`.
+///
+/// Codes of letters or other characters are recognized with templates of those
+/// characters, limited to an [`Alphabet`] if needed. Arithmetic codes such as `3+5=?`
+/// are evaluated, see [`CodeKind::Arithmetic`].
+///
+/// A recognizer is a [`CaptchaSolver`](crate::CaptchaSolver) too, so it can be set as the
+/// solver of a [`CasClient`](crate::CasClient).
+///
+/// # Example
+/// ```rust
+/// use ustc_cas::captcha::{Pipeline, Recognizer, Threshold};
+/// use ustc_cas::CasClient;
+///
+/// let recognizer = Recognizer::new().pipeline(
+///     Pipeline::new()
+///         .binarize(Threshold::Otsu)
+///         .remove_lines(2)
+///         .remove_speckles(1),
+/// );
+/// let client = CasClient::builder().captcha_solver(recognizer).build();
+/// ```
+///
+#[derive(Clone, Debug)]
+pub struct Recognizer {
+    pipeline: Pipeline,
+    templates: Templates,
+    neighbors: usize,
+    alphabet: Option<Alphabet>,
+    kind: CodeKind,
+    #[cfg(feature = "cnn")]
+    model: Option<CnnModel>,
+}
+
+impl Recognizer {
+    /// Create a recognizer with the default settings.
+    pub fn new() -> Self {
+        Self {
+            pipeline: Pipeline::default(),
+            templates: Templates::default(),
+            neighbors: 3,
+            alphabet: None,
+            kind: CodeKind::Text,
+            #[cfg(feature = "cnn")]
+            model: None,
+        }
+    }
+
+    /// Set the preprocessing pipeline. Defaults to [`Pipeline::default`].
+    #[must_use]
+    pub fn pipeline(mut self, pipeline: Pipeline) -> Self {
+        self.pipeline = pipeline;
+        self
+    }
+
+    /// Set the digit templates. Defaults to [`Templates::bundled`].
+    #[must_use]
+    pub fn templates(mut self, templates: Templates) -> Self {
+        self.templates = templates;
+        self
+    }
+
+    /// Set how many of the nearest templates vote for a digit. `1` picks the nearest
+    /// template, larger values resist a single odd template when there are several per
+    /// digit. Defaults to 3. `0` is treated as `1`.
+    #[must_use]
+    pub fn neighbors(mut self, k: usize) -> Self {
+        self.neighbors = k.max(1);
+        self
+    }
+
+    /// Only answer with characters of `alphabet`, ignoring templates of other characters.
+    /// Defaults to every character of the templates, or to [`Alphabet::arithmetic`] for
+    /// arithmetic codes.
+    #[must_use]
+    pub fn alphabet(mut self, alphabet: Alphabet) -> Self {
+        self.alphabet = Some(alphabet);
+        self
+    }
+
+    /// Set what the validate code asks for. Defaults to [`CodeKind::Text`].
+    #[must_use]
+    pub fn kind(mut self, kind: CodeKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    /// Classify digits with a convolutional network instead of templates, see
+    /// [`CnnModel`]. Requires enabling `cnn` feature.
+    #[cfg(feature = "cnn")]
+    #[must_use]
+    pub fn cnn(mut self, model: CnnModel) -> Self {
+        self.model = Some(model);
+        self
+    }
+
+    /// Recognize the validate code in an image file.
+    pub fn recognize(&self, image: &[u8]) -> Result<Recognition, CaptchaError> {
+        self.recognize_luma(&LumaImage::decode(image)?)
+    }
+
+    /// Recognize the validate code in an already decoded image.
+    ///
+    /// For an arithmetic code, an error of kind [`CaptchaErrorKind::Expression`] is
+    /// returned if the characters recognized can not be evaluated.
+    pub fn recognize_luma(&self, image: &LumaImage) -> Result<Recognition, CaptchaError> {
+        let alphabet = match (&self.alphabet, self.kind) {
+            (Some(alphabet), _) => Some(alphabet.clone()),
+            (None, CodeKind::Arithmetic) => Some(Alphabet::arithmetic()),
+            (None, CodeKind::Text) => None,
+        };
+        self.check_alphabet(alphabet.as_ref())?;
+        let symbols = alphabet.as_ref().map_or(false, |a| {
+            a.chars().iter().any(|c| !c.is_ascii_alphanumeric())
+        });
+
+        let image = self.pipeline.apply(&*image.normalize()?);
+        let digits: Vec<Digit> = segment(&image, THRESHOLD, symbols)?
+            .iter()
+            .map(|s| {
+                let m = self.classify(&image, s, alphabet.as_ref());
+                Digit {
+                    value: m.value,
+                    score: m.score,
+                    confidence: m.confidence,
+                }
+            })
+            .collect();
+        let answer = match self.kind {
+            CodeKind::Text => None,
+            CodeKind::Arithmetic => {
+                let text: String = digits.iter().map(|d| d.value).collect();
+                Some(evaluate(&text)?.to_string())
+            }
+        };
+        Ok(Recognition { digits, answer })
+    }
+}
+
+impl Recognizer {
+    fn classify(
+        &self,
+        image: &LumaImage,
+        segment: &segment::Segment,
+        alphabet: Option<&Alphabet>,
+    ) -> knn::DigitMatch {
+        #[cfg(feature = "cnn")]
+        if let Some(model) = &self.model {
+            return model.classify(image, segment, alphabet);
+        }
+        classify(image, segment, &self.templates, self.neighbors, alphabet)
+    }
+
+    /// Check there is something to classify with in `alphabet`.
+    fn check_alphabet(&self, alphabet: Option<&Alphabet>) -> Result<(), CaptchaError> {
+        let alphabet = match alphabet {
+            Some(alphabet) => alphabet,
+            None => return Ok(()),
+        };
+        #[cfg(feature = "cnn")]
+        if let Some(model) = &self.model {
+            return match model.labels().iter().any(|&c| alphabet.contains(c)) {
+                true => Ok(()),
+                false => Err(CaptchaError::with_source(
+                    CaptchaErrorKind::Model,
+                    "no label of the model is in the alphabet",
+                )),
+            };
+        }
+        match self.templates.iter().any(|t| alphabet.contains(t.value)) {
+            true => Ok(()),
+            false => Err(CaptchaError::with_source(
+                CaptchaErrorKind::Templates,
+                "no template is in the alphabet",
+            )),
+        }
+    }
+}
+
+impl Default for Recognizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+///
+/// What a validate code asks for, see [`Recognizer::kind`].
+///
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum CodeKind {
+    /// The characters shown, submitted as they are. The USTC validate code is of this
+    /// kind.
+    Text,
+    /// An arithmetic expression such as `3+5=?`, whose value is submitted. The
+    /// expression is made of non-negative integers and the operators `+`, `-`, `*` (or
+    /// `x`) and `/` with the usual precedence, optionally followed by `=` and `?`.
+    /// Division must be exact.
+    Arithmetic,
+}
+
+impl Default for CodeKind {
+    fn default() -> Self {
+        Self::Text
+    }
+}
+
+///
+/// The result of [`recognize`].
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Recognition {
+    digits: Vec<Digit>,
+    answer: Option<String>,
+}
+
+impl Recognition {
+    /// The recognized code, to be submitted to CAS. For an arithmetic code, the value
+    /// of the expression.
+    pub fn code(&self) -> String {
+        match &self.answer {
+            Some(answer) => answer.clone(),
+            None => self.text(),
+        }
+    }
+
+    /// The recognized characters. The same as [`code`](Self::code) except for arithmetic
+    /// codes, where it is the expression.
+    pub fn text(&self) -> String {
+        self.digits.iter().map(|d| d.value).collect()
+    }
+
+    /// Every recognized character, from left to right.
+    pub fn digits(&self) -> &[Digit] {
+        &self.digits
+    }
+
+    /// The confidence of the least confident digit, `0.0` if there is no digit.
+    pub fn confidence(&self) -> f32 {
+        self.digits
+            .iter()
+            .map(|d| d.confidence)
+            .reduce(f32::min)
+            .unwrap_or(0.0)
+    }
+}
+
+///
+/// A recognized character, usually a digit.
+///
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct Digit {
+    /// The digit, the label of the best matching template. `'0'` to `'9'` with the
+    /// bundled templates.
+    pub value: char,
+    /// How well the digit matches its nearest template, from `0.0` to `1.0`. With a
+    /// convolutional network, the probability of the digit.
+    pub score: f32,
+    /// The margin of `score` over the best template of any other digit, from `0.0` to
+    /// `1.0`. A small confidence means the digit is easily confused, and it is `0.0` when
+    /// the vote of the nearest templates disagrees with the single nearest one. With a
+    /// convolutional network, the margin over the next most probable digit.
+    pub confidence: f32,
+}
@@ -0,0 +1,297 @@
+use super::LumaImage;
+
+const INK: u8 = 0;
+const BACKGROUND: u8 = 255;
+
+///
+/// The way a grayscale image is turned into ink and background.
+///
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Threshold {
+    /// Pixels darker than the value are ink.
+    Fixed(u8),
+    /// Choose the threshold from the histogram of the whole image with Otsu's method.
+    /// Works with any background and ink colors, as long as the lighting is even.
+    Otsu,
+    /// Pixels darker than the mean of the surrounding `(2 * radius + 1)` square by more
+    /// than `offset` are ink. Copes with backgrounds shaded from one side to the other.
+    /// `radius` should be well above the stroke width.
+    LocalMean { radius: u32, offset: u8 },
+}
+
+///
+/// A preprocessing stage, see [`Pipeline`].
+///
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Stage {
+    /// Replace every pixel with the median of the surrounding `(2 * radius + 1)` square.
+    /// Removes salt and pepper noise, best placed before binarization.
+    Median { radius: u32 },
+    /// Binarize the image, see [`Threshold`].
+    Binarize(Threshold),
+    /// Remove ink pixels with fewer than `min_neighbors` ink pixels among their 8
+    /// neighbors. Works on binarized images.
+    RemoveSpeckles { min_neighbors: u32 },
+    /// Remove thin interference lines: ink pixels on a horizontal or vertical run of ink
+    /// no longer than `max_thickness`. Works on binarized images, strokes of the digits
+    /// must be thicker than `max_thickness`.
+    RemoveLines { max_thickness: u32 },
+}
+
+///
+/// The preprocessing applied to a captcha image before the digits are located.
+///
+/// Stages run in the order they are added. Stages working on binarized images treat
+/// pixels darker than 128 as ink, and recognition thresholds the result at 128 as well,
+/// so a pipeline without [`Stage::Binarize`] works like `Threshold::Fixed(128)`.
+///
+/// The default pipeline binarizes with [`Threshold::Otsu`] and removes ink pixels with
+/// less than two ink neighbors.
+///
+/// # Example
+/// ```rust
+/// use ustc_cas::captcha::{Pipeline, Recognizer, Threshold};
+///
+/// let pipeline = Pipeline::new()
+///     .median(1)
+///     .binarize(Threshold::LocalMean { radius: 10, offset: 20 })
+///     .remove_lines(2)
+///     .remove_speckles(1);
+/// let recognizer = Recognizer::new().pipeline(pipeline);
+/// ```
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pipeline {
+    stages: Vec<Stage>,
+}
+
+impl Pipeline {
+    /// Create an empty pipeline.
+    pub fn new() -> Self {
+        Self { stages: vec![] }
+    }
+
+    /// Append `stage`.
+    #[must_use]
+    pub fn stage(mut self, stage: Stage) -> Self {
+        self.stages.push(stage);
+        self
+    }
+
+    /// Append [`Stage::Median`].
+    #[must_use]
+    pub fn median(self, radius: u32) -> Self {
+        self.stage(Stage::Median { radius })
+    }
+
+    /// Append [`Stage::Binarize`].
+    #[must_use]
+    pub fn binarize(self, threshold: Threshold) -> Self {
+        self.stage(Stage::Binarize(threshold))
+    }
+
+    /// Append [`Stage::RemoveSpeckles`].
+    #[must_use]
+    pub fn remove_speckles(self, min_neighbors: u32) -> Self {
+        self.stage(Stage::RemoveSpeckles { min_neighbors })
+    }
+
+    /// Append [`Stage::RemoveLines`].
+    #[must_use]
+    pub fn remove_lines(self, max_thickness: u32) -> Self {
+        self.stage(Stage::RemoveLines { max_thickness })
+    }
+
+    /// The stages, in order.
+    pub fn stages(&self) -> &[Stage] {
+        &self.stages
+    }
+
+    /// Run every stage on `image`.
+    pub fn apply(&self, image: &LumaImage) -> LumaImage {
+        let mut image = image.clone();
+        for stage in &self.stages {
+            image = match *stage {
+                Stage::Median { radius } => median(&image, radius),
+                Stage::Binarize(threshold) => binarize(&image, threshold),
+                Stage::RemoveSpeckles { min_neighbors } => remove_speckles(&image, min_neighbors),
+                Stage::RemoveLines { max_thickness } => remove_lines(&image, max_thickness),
+            };
+        }
+        image
+    }
+}
+
+impl Default for Pipeline {
+    fn default() -> Self {
+        Self::new().binarize(Threshold::Otsu).remove_speckles(2)
+    }
+}
+
+/// Build an image of the same size from a function of the position.
+fn map<F: Fn(u32, u32) -> u8>(image: &LumaImage, f: F) -> LumaImage {
+    let (width, height) = (image.width(), image.height());
+    let pixels = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| f(x, y))
+        .collect();
+    LumaImage::from_raw(width, height, pixels).unwrap()
+}
+
+/// The square around (`x`, `y`), clipped by the image.
+fn window(image: &LumaImage, x: u32, y: u32, radius: u32) -> (u32, u32, u32, u32) {
+    (
+        x.saturating_sub(radius),
+        y.saturating_sub(radius),
+        (x + radius).min(image.width() - 1),
+        (y + radius).min(image.height() - 1),
+    )
+}
+
+fn is_ink(image: &LumaImage, x: u32, y: u32) -> bool {
+    image.get(x, y) < 128
+}
+
+fn median(image: &LumaImage, radius: u32) -> LumaImage {
+    map(image, |x, y| {
+        let (x0, y0, x1, y1) = window(image, x, y, radius);
+        let mut values: Vec<u8> = (y0..=y1)
+            .flat_map(|wy| (x0..=x1).map(move |wx| (wx, wy)))
+            .map(|(wx, wy)| image.get(wx, wy))
+            .collect();
+        let mid = values.len() / 2;
+        *values.select_nth_unstable(mid).1
+    })
+}
+
+fn binarize(image: &LumaImage, threshold: Threshold) -> LumaImage {
+    let ink = |is_ink: bool| if is_ink { INK } else { BACKGROUND };
+    match threshold {
+        Threshold::Fixed(t) => map(image, |x, y| ink(image.get(x, y) < t)),
+        Threshold::Otsu => {
+            let t = otsu(image);
+            map(image, |x, y| ink(image.get(x, y) <= t))
+        }
+        Threshold::LocalMean { radius, offset } => {
+            let sums = integral(image);
+            let stride = image.width() as usize + 1;
+            let sum = |x: u32, y: u32| sums[y as usize * stride + x as usize];
+            map(image, |x, y| {
+                let (x0, y0, x1, y1) = window(image, x, y, radius);
+                let total = sum(x1 + 1, y1 + 1) + sum(x0, y0) - sum(x0, y1 + 1) - sum(x1 + 1, y0);
+                let count = ((x1 - x0 + 1) * (y1 - y0 + 1)) as u64;
+                let mean = total / count;
+                ink((image.get(x, y) as u64) + (offset as u64) < mean)
+            })
+        }
+    }
+}
+
+/// The threshold maximizing the variance between ink and background, pixels not above
+/// it are ink.
+fn otsu(image: &LumaImage) -> u8 {
+    let mut histogram = [0u64; 256];
+    for &p in image.pixels() {
+        histogram[p as usize] += 1;
+    }
+    let total = image.pixels().len() as f64;
+    let sum: f64 = histogram
+        .iter()
+        .enumerate()
+        .map(|(v, &n)| v as f64 * n as f64)
+        .sum();
+
+    let (mut best, mut best_variance) = (0, -1.0);
+    let (mut weight, mut weighted_sum) = (0.0, 0.0);
+    for (t, &n) in histogram.iter().enumerate() {
+        weight += n as f64;
+        weighted_sum += t as f64 * n as f64;
+        if weight == 0.0 || weight == total {
+            continue;
+        }
+        let dark = weighted_sum / weight;
+        let light = (sum - weighted_sum) / (total - weight);
+        let variance = weight * (total - weight) * (dark - light) * (dark - light);
+        if variance > best_variance {
+            best = t;
+            best_variance = variance;
+        }
+    }
+    best as u8
+}
+
+/// The summed-area table of `image`, with an extra zero row and column at the start.
+fn integral(image: &LumaImage) -> Vec<u64> {
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let stride = width + 1;
+    let mut sums = vec![0u64; stride * (height + 1)];
+    for y in 0..height {
+        let mut row = 0;
+        for x in 0..width {
+            row += image.get(x as u32, y as u32) as u64;
+            sums[(y + 1) * stride + x + 1] = sums[y * stride + x + 1] + row;
+        }
+    }
+    sums
+}
+
+fn remove_speckles(image: &LumaImage, min_neighbors: u32) -> LumaImage {
+    map(image, |x, y| {
+        if !is_ink(image, x, y) {
+            return image.get(x, y);
+        }
+        let (x0, y0, x1, y1) = window(image, x, y, 1);
+        let neighbors = (y0..=y1)
+            .flat_map(|wy| (x0..=x1).map(move |wx| (wx, wy)))
+            .filter(|&(wx, wy)| (wx, wy) != (x, y) && is_ink(image, wx, wy))
+            .count() as u32;
+        if neighbors < min_neighbors {
+            BACKGROUND
+        } else {
+            image.get(x, y)
+        }
+    })
+}
+
+fn remove_lines(image: &LumaImage, max_thickness: u32) -> LumaImage {
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    // length of the horizontal and vertical ink run every pixel lies on
+    let mut across = vec![0u32; width * height];
+    let mut down = vec![0u32; width * height];
+    for y in 0..height {
+        let mut x = 0;
+        while x < width {
+            let start = x;
+            while x < width && is_ink(image, x as u32, y as u32) {
+                x += 1;
+            }
+            for i in start..x {
+                across[y * width + i] = (x - start) as u32;
+            }
+            x += 1;
+        }
+    }
+    for x in 0..width {
+        let mut y = 0;
+        while y < height {
+            let start = y;
+            while y < height && is_ink(image, x as u32, y as u32) {
+                y += 1;
+            }
+            for i in start..y {
+                down[i * width + x] = (y - start) as u32;
+            }
+            y += 1;
+        }
+    }
+
+    map(image, |x, y| {
+        let i = y as usize * width + x as usize;
+        if is_ink(image, x, y) && across[i].min(down[i]) <= max_thickness {
+            BACKGROUND
+        } else {
+            image.get(x, y)
+        }
+    })
+}
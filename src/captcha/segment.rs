@@ -0,0 +1,133 @@
+use super::template::{GLYPH_HEIGHT, GLYPH_WIDTH};
+use super::{CaptchaError, CaptchaErrorKind, LumaImage};
+
+/// the most digits a validate code is expected to have.
+const MAX_DIGITS: usize = 8;
+
+/// ink runs narrower than this are taken as noise.
+const MIN_WIDTH: u32 = 3;
+
+/// digits shorter than this are implausible.
+const MIN_HEIGHT: u32 = 8;
+
+/// parts of a symbol split by at most this many rows, like the bars of `=`, are joined.
+const MAX_GAP: u32 = 5;
+
+/// The bounding box of a digit.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Segment {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+///
+/// Find the digits in `image` by the column projection of its ink.
+///
+/// Every run of columns holding ink is a digit, runs too wide for one digit are split
+/// evenly, as digits may touch each other. An error of kind
+/// [`Segmentation`](CaptchaErrorKind::Segmentation) is returned if the result does not
+/// look like a validate code.
+///
+/// With `symbols`, glyphs shorter than a digit such as `-` are accepted, and a glyph may
+/// be made of parts one above the other, such as `=`.
+///
+pub(crate) fn segment(
+    image: &LumaImage,
+    threshold: u8,
+    symbols: bool,
+) -> Result<Vec<Segment>, CaptchaError> {
+    let ink = |x, y| image.get(x, y) < threshold;
+    let columns: Vec<bool> = (0..image.width())
+        .map(|x| (0..image.height()).any(|y| ink(x, y)))
+        .collect();
+
+    let columns = runs(&columns);
+
+    let mut segments = vec![];
+    for (x, width) in columns.into_iter().filter(|&(_, w)| w >= MIN_WIDTH) {
+        let n = ((width + GLYPH_WIDTH / 2) / GLYPH_WIDTH).max(1);
+        for i in 0..n {
+            let left = x + width * i / n;
+            let right = x + width * (i + 1) / n;
+            // noise above or below the digit is left out by taking the tallest run of rows
+            let rows: Vec<bool> = (0..image.height())
+                .map(|y| (left..right).any(|x| ink(x, y)))
+                .collect();
+            let mut rows = runs(&rows);
+            if symbols {
+                rows = join(rows);
+            }
+            if let Some((top, height)) = rows.into_iter().max_by_key(|r| r.1) {
+                segments.push(Segment {
+                    x: left,
+                    y: top,
+                    width: right - left,
+                    height,
+                });
+            }
+        }
+    }
+
+    check(image, &segments, if symbols { 1 } else { MIN_HEIGHT })?;
+    Ok(segments)
+}
+
+/// The start and length of every run of `true`.
+fn runs(flags: &[bool]) -> Vec<(u32, u32)> {
+    let mut runs = vec![];
+    let mut start = None;
+    for (i, &flag) in flags.iter().chain(&[false]).enumerate() {
+        match (start, flag) {
+            (None, true) => start = Some(i as u32),
+            (Some(s), false) => {
+                runs.push((s, i as u32 - s));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    runs
+}
+
+/// Join runs split by at most `MAX_GAP`.
+fn join(runs: Vec<(u32, u32)>) -> Vec<(u32, u32)> {
+    let mut joined: Vec<(u32, u32)> = vec![];
+    for (start, len) in runs {
+        match joined.last_mut() {
+            Some(last) if start - (last.0 + last.1) <= MAX_GAP => last.1 = start + len - last.0,
+            _ => joined.push((start, len)),
+        }
+    }
+    joined
+}
+
+fn check(image: &LumaImage, segments: &[Segment], min_height: u32) -> Result<(), CaptchaError> {
+    let error = |msg: &str| {
+        Err(CaptchaError::with_source(
+            CaptchaErrorKind::Segmentation,
+            msg,
+        ))
+    };
+
+    if segments.is_empty() {
+        return error("no digit found");
+    }
+    if segments.len() > MAX_DIGITS {
+        return error("too many digits");
+    }
+    for s in segments {
+        if s.x == 0
+            || s.y == 0
+            || s.x + s.width >= image.width()
+            || s.y + s.height >= image.height()
+        {
+            return error("digit cut off by the image border");
+        }
+        if s.height < min_height || s.height > GLYPH_HEIGHT + 2 {
+            return error("digit height out of range");
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,291 @@
+use super::{CaptchaError, CaptchaErrorKind, LumaImage};
+use once_cell::sync::Lazy;
+use std::path::Path;
+use std::sync::Arc;
+
+/// width and height of a digit.
+pub(crate) const GLYPH_WIDTH: u32 = 15;
+pub(crate) const GLYPH_HEIGHT: u32 = 21;
+
+/// pixels darker than this are ink.
+pub(crate) const THRESHOLD: u8 = 128;
+
+/// `u64` words holding the pixels of a glyph.
+const WORDS: usize = ((GLYPH_WIDTH * GLYPH_HEIGHT + 63) / 64) as usize;
+
+/// A binarized 15x21 glyph. The rows are packed back to back into `u64` words, so
+/// comparing glyphs is a handful of xor and popcount.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Glyph {
+    words: [u64; WORDS],
+}
+
+impl Glyph {
+    /// Binarize the 15x21 window with its top left corner at (`x`, `y`). The window may
+    /// exceed the image, pixels outside count as background.
+    pub(crate) fn from_window(image: &LumaImage, x: i32, y: i32, threshold: u8) -> Self {
+        let mut glyph = Self::default();
+        for row in 0..GLYPH_HEIGHT {
+            let py = y + row as i32;
+            if py < 0 || py >= image.height() as i32 {
+                continue;
+            }
+            for col in 0..GLYPH_WIDTH {
+                let px = x + col as i32;
+                if px >= 0
+                    && px < image.width() as i32
+                    && image.get(px as u32, py as u32) < threshold
+                {
+                    glyph.set(col, row);
+                }
+            }
+        }
+        glyph
+    }
+
+    /// A glyph with every pixel set.
+    pub(crate) fn full() -> Self {
+        let mut glyph = Self::default();
+        for row in 0..GLYPH_HEIGHT {
+            glyph.set_row(row, (1 << GLYPH_WIDTH) - 1);
+        }
+        glyph
+    }
+
+    fn bit(x: u32, y: u32) -> (usize, u64) {
+        let i = (y * GLYPH_WIDTH + x) as usize;
+        (i / 64, 1 << (i % 64))
+    }
+
+    pub(crate) fn get(&self, x: u32, y: u32) -> bool {
+        let (word, mask) = Self::bit(x, y);
+        self.words[word] & mask != 0
+    }
+
+    pub(crate) fn set(&mut self, x: u32, y: u32) {
+        let (word, mask) = Self::bit(x, y);
+        self.words[word] |= mask;
+    }
+
+    /// Row `y`, with bit `x` set for pixel (`x`, `y`).
+    fn row(&self, y: u32) -> u16 {
+        (0..GLYPH_WIDTH)
+            .filter(|&x| self.get(x, y))
+            .fold(0, |row, x| row | 1 << x)
+    }
+
+    fn set_row(&mut self, y: u32, row: u16) {
+        for x in (0..GLYPH_WIDTH).filter(|x| row >> x & 1 == 1) {
+            self.set(x, y);
+        }
+    }
+
+    fn count(&self) -> u32 {
+        self.words.iter().map(|w| w.count_ones()).sum()
+    }
+
+    /// The number of pixels differing from `other`.
+    pub(crate) fn distance(&self, other: &Glyph) -> u32 {
+        self.masked_distance(other, &Glyph::full())
+    }
+
+    /// The number of pixels set in `mask` differing from `other`.
+    fn masked_distance(&self, other: &Glyph, mask: &Glyph) -> u32 {
+        self.words
+            .iter()
+            .zip(&other.words)
+            .zip(&mask.words)
+            .map(|((a, b), m)| ((a ^ b) & m).count_ones())
+            .sum()
+    }
+}
+
+/// The glyph of a digit, and which of its pixels are known.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Template {
+    pub(crate) value: char,
+    ink: Glyph,
+    known: Glyph,
+    known_count: u32,
+    /// left and top edges of the ink.
+    pub(crate) left: i32,
+    pub(crate) top: i32,
+}
+
+impl Template {
+    pub(crate) fn new(value: char, ink: Glyph, known: Glyph) -> Self {
+        let left = (0..GLYPH_WIDTH)
+            .find(|&x| (0..GLYPH_HEIGHT).any(|y| ink.get(x, y)))
+            .unwrap_or(0);
+        let top = (0..GLYPH_HEIGHT)
+            .find(|&y| (0..GLYPH_WIDTH).any(|x| ink.get(x, y)))
+            .unwrap_or(0);
+        Self {
+            value,
+            ink,
+            known,
+            known_count: known.count(),
+            left: left as i32,
+            top: top as i32,
+        }
+    }
+
+    /// Fraction of known pixels agreeing with `glyph`.
+    pub(crate) fn score(&self, glyph: &Glyph) -> f32 {
+        let differ = self.ink.masked_distance(glyph, &self.known);
+        1.0 - differ as f32 / self.known_count.max(1) as f32
+    }
+}
+
+const MAGIC: &[u8; 4] = b"UCTP";
+const VERSION: u8 = 1;
+
+/// The bundled templates were captured by an old reader which stopped after the first
+/// 256 pixels, so only those pixels are known. The bottom rows should only be marked
+/// known once they are trained from real validate codes, e.g. collected with
+/// [`CorpusRecorder`](crate::CorpusRecorder).
+static BUNDLED: Lazy<Templates> = Lazy::new(|| {
+    Templates::from_bytes(include_bytes!("templates.bin")).expect("bundled templates are valid")
+});
+
+///
+/// A set of digit templates, used by [`Recognizer`](super::Recognizer) to tell the
+/// digits apart.
+///
+/// A set of templates for the USTC validate code is bundled with the crate. Updated
+/// templates can be loaded at runtime with [`Templates::load`] or
+/// [`Templates::from_bytes`], without recompiling.
+///
+/// # Format
+/// Templates are stored in a small binary file, all integers little endian:
+///
+/// - magic `UCTP`, then the format version `1`, glyph width `15`, glyph height `21`
+///   and the number of templates, one byte each.
+/// - for every template, the digit as an ASCII byte, then 21 `u16` rows of ink and
+///   21 `u16` rows telling which pixels are known. Bit `x` of a row is column `x`.
+///
+/// A digit may have any number of templates, up to 255 templates in total. A template
+/// must know at least one pixel.
+///
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Templates {
+    templates: Arc<Vec<Template>>,
+}
+
+impl Templates {
+    /// The templates bundled with the crate.
+    pub fn bundled() -> Self {
+        BUNDLED.clone()
+    }
+
+    /// Parse templates in the format described above.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, CaptchaError> {
+        let error = |msg: &str| CaptchaError::with_source(CaptchaErrorKind::Templates, msg);
+
+        let (header, mut data) = data.split_at(data.len().min(8));
+        if header.len() < 8 || &header[..4] != MAGIC {
+            return Err(error("not a template file"));
+        }
+        if header[4] != VERSION {
+            return Err(error("unsupported template file version"));
+        }
+        if (header[5], header[6]) != (GLYPH_WIDTH as u8, GLYPH_HEIGHT as u8) {
+            return Err(error("unsupported glyph size"));
+        }
+
+        let count = header[7] as usize;
+        let size = 1 + 4 * GLYPH_HEIGHT as usize;
+        if count == 0 || data.len() != count * size {
+            return Err(error("wrong number of templates"));
+        }
+
+        let mut templates = Vec::with_capacity(count);
+        for _ in 0..count {
+            let (entry, rest) = data.split_at(size);
+            data = rest;
+            let value = entry[0] as char;
+            if !value.is_ascii_graphic() {
+                return Err(error("template label is not a printable character"));
+            }
+            let mut glyphs = [Glyph::default(); 2];
+            let mut rows = entry[1..]
+                .chunks(2)
+                .map(|b| u16::from_le_bytes([b[0], b[1]]));
+            for glyph in &mut glyphs {
+                for y in 0..GLYPH_HEIGHT {
+                    let row = rows.next().unwrap_or(0);
+                    if row >> GLYPH_WIDTH != 0 {
+                        return Err(error("template row wider than the glyph"));
+                    }
+                    glyph.set_row(y, row);
+                }
+            }
+            let [ink, known] = glyphs;
+            if known == Glyph::default() {
+                return Err(error("template knows no pixel"));
+            }
+            templates.push(Template::new(value, ink, known));
+        }
+        Ok(Self {
+            templates: Arc::new(templates),
+        })
+    }
+
+    pub(crate) fn from_templates(templates: Vec<Template>) -> Self {
+        Self {
+            templates: Arc::new(templates),
+        }
+    }
+
+    /// Read templates from a file, see [`Templates::from_bytes`].
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, CaptchaError> {
+        let data = std::fs::read(path)
+            .map_err(|e| CaptchaError::with_source(CaptchaErrorKind::Templates, e))?;
+        Self::from_bytes(&data)
+    }
+
+    /// Write the templates in the format described above.
+    ///
+    /// Returns an error of kind [`Templates`](CaptchaErrorKind::Templates) if there are
+    /// more than 255 templates, or a label is not printable ASCII, which the format
+    /// cannot hold.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, CaptchaError> {
+        let error = |msg: &str| CaptchaError::with_source(CaptchaErrorKind::Templates, msg);
+
+        let count =
+            u8::try_from(self.templates.len()).map_err(|_| error("more than 255 templates"))?;
+        let mut data = MAGIC.to_vec();
+        data.extend([VERSION, GLYPH_WIDTH as u8, GLYPH_HEIGHT as u8, count]);
+        for template in self.templates.iter() {
+            if !template.value.is_ascii_graphic() {
+                return Err(error("template label is not a printable character"));
+            }
+            data.push(template.value as u8);
+            for glyph in [&template.ink, &template.known] {
+                for y in 0..GLYPH_HEIGHT {
+                    data.extend(glyph.row(y).to_le_bytes());
+                }
+            }
+        }
+        Ok(data)
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &Template> {
+        self.templates.iter()
+    }
+
+    /// The number of templates.
+    pub fn len(&self) -> usize {
+        self.templates.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.templates.is_empty()
+    }
+}
+
+impl Default for Templates {
+    fn default() -> Self {
+        Self::bundled()
+    }
+}
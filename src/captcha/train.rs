@@ -0,0 +1,201 @@
+use super::segment::{segment, Segment};
+use super::template::{Glyph, Template, GLYPH_HEIGHT, GLYPH_WIDTH, THRESHOLD};
+use super::{CaptchaError, CaptchaErrorKind, LumaImage, Pipeline, Templates};
+use std::collections::BTreeMap;
+
+/// rounds of k-means when clustering.
+const ROUNDS: usize = 10;
+
+///
+/// Build [`Templates`] from labeled captcha images.
+///
+/// Every sample is preprocessed and segmented exactly like [`Recognizer`](super::Recognizer)
+/// does, and each segment is paired with its character of the label. The glyphs of a
+/// digit are then averaged into one template, or clustered into several templates with
+/// [`TemplateTrainer::clusters`] when a digit is drawn in different ways.
+///
+/// The `ustc-cas-captcha-train` binary, enabled by `train` feature, runs this over a
+/// directory of images.
+///
+/// # Example
+/// ```rust
+/// use ustc_cas::captcha::{LumaImage, Recognizer, TemplateTrainer};
+///
+/// # fn run(samples: Vec<(LumaImage, String)>) -> Result<(), ustc_cas::captcha::CaptchaError> {
+/// let mut trainer = TemplateTrainer::new();
+/// for (image, label) in &samples {
+///     trainer.add(image, label)?;
+/// }
+/// let recognizer = Recognizer::new().templates(trainer.build()?);
+/// # Ok(())
+/// # }
+/// ```
+///
+#[derive(Clone, Debug)]
+pub struct TemplateTrainer {
+    pipeline: Pipeline,
+    clusters: usize,
+    glyphs: BTreeMap<char, Vec<Glyph>>,
+}
+
+impl TemplateTrainer {
+    /// Create a trainer with the default pipeline, averaging the glyphs of each digit.
+    pub fn new() -> Self {
+        Self {
+            pipeline: Pipeline::default(),
+            clusters: 1,
+            glyphs: BTreeMap::new(),
+        }
+    }
+
+    /// Set the preprocessing pipeline. It should be the one the templates are going to
+    /// be used with. Defaults to [`Pipeline::default`].
+    #[must_use]
+    pub fn pipeline(mut self, pipeline: Pipeline) -> Self {
+        self.pipeline = pipeline;
+        self
+    }
+
+    /// Build up to `k` templates for each digit by clustering its glyphs. Defaults to 1.
+    ///
+    /// The templates of all digits together must not exceed 255, see
+    /// [`TemplateTrainer::build`].
+    #[must_use]
+    pub fn clusters(mut self, k: usize) -> Self {
+        self.clusters = k.max(1);
+        self
+    }
+
+    /// Add a sample showing the code `label`.
+    ///
+    /// The image is scaled like [`Recognizer`](super::Recognizer) does. Returns an error
+    /// of kind [`InvalidSize`](CaptchaErrorKind::InvalidSize) if its size is not accepted,
+    /// of kind [`Segmentation`](CaptchaErrorKind::Segmentation) if the image does not
+    /// segment into as many digits as `label` has, or of kind
+    /// [`Templates`](CaptchaErrorKind::Templates) if `label` is not printable ASCII. In
+    /// any case nothing is added.
+    pub fn add(&mut self, image: &LumaImage, label: &str) -> Result<(), CaptchaError> {
+        let (image, segments) = labeled_segments(&self.pipeline, image, label)?;
+        for (s, value) in segments.iter().zip(label.chars()) {
+            let glyph = Glyph::from_window(&image, s.x as i32, s.y as i32, THRESHOLD);
+            self.glyphs.entry(value).or_default().push(glyph);
+        }
+        Ok(())
+    }
+
+    /// The number of glyphs collected for each digit.
+    pub fn counts(&self) -> BTreeMap<char, usize> {
+        self.glyphs.iter().map(|(&c, g)| (c, g.len())).collect()
+    }
+
+    /// Build the templates from the samples added so far.
+    ///
+    /// Returns an error of kind [`Templates`](CaptchaErrorKind::Templates) if no sample
+    /// was added, or if there would be more than 255 templates, which
+    /// [`Templates::to_bytes`] cannot write. Use fewer clusters then.
+    pub fn build(&self) -> Result<Templates, CaptchaError> {
+        let known = Glyph::full();
+        let templates: Vec<_> = self
+            .glyphs
+            .iter()
+            .flat_map(|(&value, glyphs)| {
+                cluster(glyphs, self.clusters)
+                    .into_iter()
+                    .map(move |ink| Template::new(value, ink, known))
+            })
+            .collect();
+        if templates.is_empty() || templates.len() > u8::MAX as usize {
+            return Err(CaptchaError::with_source(
+                CaptchaErrorKind::Templates,
+                format!("{} templates built, 1 to 255 needed", templates.len()),
+            ));
+        }
+        Ok(Templates::from_templates(templates))
+    }
+}
+
+impl Default for TemplateTrainer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Preprocess and segment a sample showing `label`, checking there is a segment for
+/// every character of the label. Errors are of kind `Segmentation`, or of `Templates` for
+/// labels which are not printable ASCII.
+pub(crate) fn labeled_segments(
+    pipeline: &Pipeline,
+    image: &LumaImage,
+    label: &str,
+) -> Result<(LumaImage, Vec<Segment>), CaptchaError> {
+    if !label.chars().all(|c| c.is_ascii_graphic()) {
+        return Err(CaptchaError::with_source(
+            CaptchaErrorKind::Templates,
+            "label is not printable ASCII",
+        ));
+    }
+    let image = pipeline.apply(&*image.normalize()?);
+    // labels of arithmetic codes hold symbols, which are segmented like `Recognizer` does
+    // in arithmetic mode
+    let symbols = !label.chars().all(|c| c.is_ascii_alphanumeric());
+    let segments = segment(&image, THRESHOLD, symbols)?;
+    if segments.len() != label.chars().count() {
+        return Err(CaptchaError::with_source(
+            CaptchaErrorKind::Segmentation,
+            format!("{} digits found for label {label}", segments.len()),
+        ));
+    }
+    Ok((image, segments))
+}
+
+/// Pixels set in more than half of `glyphs`.
+fn majority(glyphs: &[&Glyph]) -> Glyph {
+    let mut out = Glyph::default();
+    for y in 0..GLYPH_HEIGHT {
+        for x in 0..GLYPH_WIDTH {
+            let count = glyphs.iter().filter(|g| g.get(x, y)).count();
+            if count * 2 > glyphs.len() {
+                out.set(x, y);
+            }
+        }
+    }
+    out
+}
+
+/// Cluster `glyphs` into at most `k` groups by k-means on Hamming distance, and return
+/// the center of every non-empty group.
+fn cluster(glyphs: &[Glyph], k: usize) -> Vec<Glyph> {
+    // start from the glyphs farthest from each other, so the result is deterministic
+    let mut centers = vec![glyphs[0]];
+    while centers.len() < k.min(glyphs.len()) {
+        let farthest = glyphs
+            .iter()
+            .max_by_key(|g| centers.iter().map(|c| c.distance(g)).min())
+            .copied()
+            .unwrap();
+        if centers.contains(&farthest) {
+            break;
+        }
+        centers.push(farthest);
+    }
+
+    for _ in 0..ROUNDS {
+        let mut groups = vec![vec![]; centers.len()];
+        for glyph in glyphs {
+            let nearest = (0..centers.len())
+                .min_by_key(|&i| centers[i].distance(glyph))
+                .unwrap();
+            groups[nearest].push(glyph);
+        }
+        let next: Vec<_> = groups
+            .iter()
+            .filter(|g| !g.is_empty())
+            .map(|g| majority(g))
+            .collect();
+        if next == centers {
+            break;
+        }
+        centers = next;
+    }
+    centers
+}
@@ -0,0 +1,51 @@
+use super::*;
+use std::fmt::{Debug, Formatter};
+
+///
+/// The result of [`CasClient::start_login`], the first phase of a two-phase login.
+///
+#[derive(Debug)]
+pub enum LoginStep {
+    /// No validate code was requested and the login succeeded with this ticket.
+    Ticket(String),
+    /// A validate code is requested, answer it with [`CasClient::submit_captcha`].
+    Captcha(CaptchaChallenge),
+}
+
+///
+/// A validate code waiting to be answered, together with the login it belongs to.
+///
+/// Returned by [`CasClient::start_login`]. Show [`image`](CaptchaChallenge::image) to
+/// whoever can read it, then pass the answer to [`CasClient::submit_captcha`] to finish
+/// the login. The challenge keeps the CAS session of the login, so it must be answered
+/// with a client for the same CAS server, and should be answered soon before CAS forgets
+/// the session.
+///
+/// This works without any captcha solver, and thus without `validate-code` feature.
+///
+pub struct CaptchaChallenge {
+    pub(crate) image: Vec<u8>,
+    pub(crate) form: HashMap<String, String>,
+    pub(crate) jar: Jar,
+}
+
+impl CaptchaChallenge {
+    /// The raw validate code image, usually a JPEG file.
+    pub fn image(&self) -> &[u8] {
+        &self.image
+    }
+
+    /// The username logging in.
+    pub fn username(&self) -> &str {
+        self.form.get("username").map_or("", |s| s.as_str())
+    }
+}
+
+impl Debug for CaptchaChallenge {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CaptchaChallenge")
+            .field("username", &self.username())
+            .field("image", &format_args!("[{} bytes]", self.image.len()))
+            .finish_non_exhaustive()
+    }
+}
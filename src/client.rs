@@ -0,0 +1,554 @@
+use super::*;
+use reqwest::{ClientBuilder, Proxy};
+use std::fmt::{Debug, Formatter};
+use std::time::Duration;
+
+///
+/// A reusable client for logging into USTC CAS System.
+///
+/// Unlike [`get_ticket`](crate::get_ticket), which uses a hidden default instance, a `CasClient`
+/// owns its own HTTP client, so timeouts, proxies, TLS roots and the like can be configured
+/// through [`CasClientBuilder`].
+///
+/// `CasClient` is cheap to clone, and clones share the same connection pool.
+///
+/// Cookies are never shared: each call to `get_ticket` starts with an empty cookie jar,
+/// so logging into different accounts with one client, even concurrently, can not leak
+/// a CAS session from one account to another.
+///
+/// # Example
+/// ```rust
+/// use std::time::Duration;
+/// use ustc_cas::CasClient;
+///
+/// # async fn run() -> Result<(), ustc_cas::CasError> {
+/// let client = CasClient::builder()
+///     .timeout(Duration::from_secs(10))
+///     .build()?;
+///
+/// let ticket = client
+///     .get_ticket("PB00000000", "12345678", "https://jw.ustc.edu.cn/ucas-sso/login")
+///     .await?;
+/// println!("ticket: {ticket}");
+/// # Ok(())
+/// # }
+/// ```
+///
+#[derive(Clone)]
+pub struct CasClient {
+    client: Client,
+    login_url: String,
+    image_url: String,
+    validate_url: String,
+    max_attempts: u32,
+    min_confidence: f32,
+    max_refreshes: u32,
+    solver: Option<Arc<dyn AsyncCaptchaSolver>>,
+    recorder: Option<Arc<dyn CaptchaRecorder>>,
+}
+
+///
+/// A builder to construct a [`CasClient`] with custom configuration.
+///
+/// Redirect policy and cookie handling are managed by the client itself and can not
+/// be changed. Cookies are never shared between logins, see [`CasClient`].
+///
+#[must_use]
+pub struct CasClientBuilder {
+    inner: ClientBuilder,
+    base_url: String,
+    protocol: CasProtocol,
+    max_attempts: u32,
+    min_confidence: f32,
+    max_refreshes: u32,
+    solver: Option<Arc<dyn AsyncCaptchaSolver>>,
+    recorder: Option<Arc<dyn CaptchaRecorder>>,
+}
+
+impl CasClient {
+    /// Create a [`CasClientBuilder`] with default configuration.
+    pub fn builder() -> CasClientBuilder {
+        CasClientBuilder::new()
+    }
+
+    ///
+    /// log into USTC CAS System and get ticket value.
+    ///
+    /// If CAS rejects the recognized validate code, the login is retried with a new one,
+    /// up to [`CasClientBuilder::max_attempts`] times. [`CasError::attempts`] tells how many
+    /// attempts were made when it fails.
+    ///
+    /// If validate code is requested but there is no captcha solver, an error of kind
+    /// [`ErrorKind::CaptchaRequired`] is returned, see [`CasClientBuilder::captcha_solver`]
+    /// and [`start_login`](Self::start_login).
+    ///
+    pub async fn get_ticket<U, P, S>(
+        &self,
+        username: U,
+        password: P,
+        service_url: S,
+    ) -> Result<String, CasError>
+    where
+        U: AsRef<str>,
+        P: AsRef<str>,
+        S: AsRef<str>,
+    {
+        let username = username.as_ref();
+        let password = password.as_ref();
+        let service_url = service_url.as_ref();
+
+        let jar = Jar::default();
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            match self.login(&jar, username, password, service_url).await {
+                Err(e)
+                    if e.kind() == ErrorKind::ValidateCodeIncorrect
+                        && attempts < self.max_attempts => {}
+                result => return result.map_err(|e| e.with_attempts(attempts)),
+            }
+        }
+    }
+
+    ///
+    /// Start a two-phase login, for solving the validate code without a captcha solver.
+    ///
+    /// If CAS does not ask for a validate code, the login finishes right away with
+    /// [`LoginStep::Ticket`]. Otherwise [`LoginStep::Captcha`] carries the validate code
+    /// image, and [`submit_captcha`](Self::submit_captcha) finishes the login with the
+    /// answer. The captcha solver of the client is never used.
+    ///
+    /// # Example
+    /// ```rust
+    /// use ustc_cas::{CasClient, LoginStep};
+    ///
+    /// # async fn run() -> Result<(), ustc_cas::CasError> {
+    /// let client = CasClient::builder().build()?;
+    /// let service = "https://jw.ustc.edu.cn/ucas-sso/login";
+    ///
+    /// let ticket = match client.start_login("PB00000000", "12345678", service).await? {
+    ///     LoginStep::Ticket(ticket) => ticket,
+    ///     LoginStep::Captcha(challenge) => {
+    ///         std::fs::write("validatecode.jpg", challenge.image()).unwrap();
+    ///         let mut code = String::new();
+    ///         std::io::stdin().read_line(&mut code).unwrap();
+    ///         client.submit_captcha(challenge, code.trim()).await?
+    ///     }
+    /// };
+    /// println!("ticket: {ticket}");
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    pub async fn start_login<U, P, S>(
+        &self,
+        username: U,
+        password: P,
+        service_url: S,
+    ) -> Result<LoginStep, CasError>
+    where
+        U: AsRef<str>,
+        P: AsRef<str>,
+        S: AsRef<str>,
+    {
+        let jar = Jar::default();
+        let form = self
+            .login_form(
+                &jar,
+                username.as_ref(),
+                password.as_ref(),
+                service_url.as_ref(),
+            )
+            .await?;
+        if need_validate_code(&form) {
+            let image = self.validate_code_image(&jar).await?;
+            Ok(LoginStep::Captcha(CaptchaChallenge { image, form, jar }))
+        } else {
+            self.submit(&jar, form).await.map(LoginStep::Ticket)
+        }
+    }
+
+    ///
+    /// Finish a login started by [`start_login`](Self::start_login) with the validate code
+    /// read from the challenge image.
+    ///
+    /// If CAS rejects the code, an error of kind [`ErrorKind::ValidateCodeIncorrect`] is
+    /// returned and the login has to be started over.
+    ///
+    pub async fn submit_captcha<C: AsRef<str>>(
+        &self,
+        challenge: CaptchaChallenge,
+        code: C,
+    ) -> Result<String, CasError> {
+        let CaptchaChallenge {
+            image,
+            mut form,
+            jar,
+        } = challenge;
+        form.insert("LT".into(), code.as_ref().into());
+        let result = self.submit(&jar, form).await;
+        let code = code.as_ref();
+        record_captcha(&self.recorder, &image, code, code, &result);
+        result
+    }
+
+    ///
+    /// validate a service ticket and get the user it was issued to.
+    ///
+    /// `service_url` must be the one the ticket was issued for. A ticket can be validated
+    /// only once, CAS rejects it afterwards with an error of kind
+    /// [`ErrorKind::TicketInvalid`]. If CAS does not accept `service_url`, the error is
+    /// of kind [`ErrorKind::ServiceUrlIncorrect`]. The message of CAS, if any, is kept in
+    /// [`CasError::message`].
+    ///
+    /// The protocol used is set with [`CasClientBuilder::cas_protocol`].
+    ///
+    /// # Example
+    /// ```rust
+    /// use ustc_cas::CasClient;
+    ///
+    /// # async fn run() -> Result<(), ustc_cas::CasError> {
+    /// let client = CasClient::builder().build()?;
+    /// let service = "https://jw.ustc.edu.cn/ucas-sso/login";
+    ///
+    /// let ticket = client.get_ticket("PB00000000", "12345678", service).await?;
+    /// let principal = client.validate_ticket(&ticket, service).await?;
+    /// println!("user: {}", principal.user());
+    /// for (name, values) in principal.attributes() {
+    ///     println!("{name}: {values:?}");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    pub async fn validate_ticket<T, S>(
+        &self,
+        ticket: T,
+        service_url: S,
+    ) -> Result<Principal, CasError>
+    where
+        T: AsRef<str>,
+        S: AsRef<str>,
+    {
+        let rsps = self
+            .client
+            .get(&self.validate_url)
+            .query(&[
+                ("service", service_url.as_ref()),
+                ("ticket", ticket.as_ref()),
+            ])
+            .send()
+            .await?
+            .error_for_status()?;
+        parse_validation(&rsps.text().await?)
+    }
+
+    /// a single login attempt, fetching a fresh login page and validate code.
+    async fn login(
+        &self,
+        jar: &Jar,
+        username: &str,
+        password: &str,
+        service_url: &str,
+    ) -> Result<String, CasError> {
+        let mut form = self
+            .login_form(jar, username, password, service_url)
+            .await?;
+        let mut captcha = None;
+        if need_validate_code(&form) {
+            let solver = self
+                .solver
+                .as_ref()
+                .ok_or(CasError::new(ErrorKind::CaptchaRequired))?;
+            let (image, solution) = self.solve_captcha(jar, solver.as_ref()).await?;
+            form.insert("LT".into(), solution.code.clone());
+            captcha = Some((image, solution));
+        }
+        let result = self.submit(jar, form).await;
+        if let Some((image, solution)) = captcha {
+            record_captcha(
+                &self.recorder,
+                &image,
+                &solution.text,
+                &solution.code,
+                &result,
+            );
+        }
+        result
+    }
+
+    /// fetch and solve the validate code, fetching a new one while the solver is unsure or fails.
+    async fn solve_captcha(
+        &self,
+        jar: &Jar,
+        solver: &dyn AsyncCaptchaSolver,
+    ) -> Result<(Vec<u8>, Solution), CasError> {
+        let mut refreshes = 0;
+        loop {
+            let image = self.validate_code_image(jar).await?;
+            // a code the solver cannot read at all is as unsure as it gets
+            match solver.solve_with_text(&image).await {
+                Ok(solution)
+                    if solution.confidence >= self.min_confidence
+                        || refreshes >= self.max_refreshes =>
+                {
+                    return Ok((image, solution));
+                }
+                Err(err) if refreshes >= self.max_refreshes => {
+                    return Err(CasError::from_solver(err));
+                }
+                _ => refreshes += 1,
+            }
+        }
+    }
+
+    /// fetch the login page and fill in the login form.
+    async fn login_form(
+        &self,
+        jar: &Jar,
+        username: &str,
+        password: &str,
+        service_url: &str,
+    ) -> Result<HashMap<String, String>, CasError> {
+        let rsps = self
+            .send(
+                jar,
+                self.client
+                    .get(format!("{}?service={service_url}", self.login_url)),
+            )
+            .await?
+            .error_for_status()?;
+
+        let text = rsps.text().await?;
+        let cas_lt = get_cas_lt(&text)?.into();
+        let mut form = get_form(text)?;
+        form.insert("username".into(), username.into());
+        form.insert("password".into(), password.into());
+        form.insert("CAS_LT".into(), cas_lt);
+        Ok(form)
+    }
+
+    async fn validate_code_image(&self, jar: &Jar) -> Result<Vec<u8>, CasError> {
+        let rsps = self
+            .send(jar, self.client.get(&self.image_url))
+            .await?
+            .error_for_status()?;
+        Ok(rsps.bytes().await?.to_vec())
+    }
+
+    /// post the login form and get the ticket.
+    async fn submit(
+        &self,
+        jar: &Jar,
+        mut form: HashMap<String, String>,
+    ) -> Result<String, CasError> {
+        form.insert("button".into(), "".into());
+
+        let rsps = self
+            .send(jar, self.client.post(&self.login_url).form(&form))
+            .await?
+            .error_for_status()?;
+
+        if rsps.headers().contains_key(LOCATION) {
+            match_ticket(rsps.headers())
+        } else {
+            Err(login_error(&rsps.text().await?))
+        }
+    }
+
+    /// send a request with cookies from `jar`, and store the cookies set by the response back.
+    async fn send(&self, jar: &Jar, request: RequestBuilder) -> Result<Response, CasError> {
+        let mut request = request.build()?;
+        if let Some(cookies) = jar.cookies(request.url()) {
+            request.headers_mut().insert(COOKIE, cookies);
+        }
+        let rsps = self.client.execute(request).await?;
+        jar.set_cookies(&mut rsps.headers().get_all(SET_COOKIE).iter(), rsps.url());
+        Ok(rsps)
+    }
+}
+
+impl CasClientBuilder {
+    /// Create a builder with default configuration.
+    pub fn new() -> Self {
+        Self {
+            inner: Client::builder().user_agent(USER_AGENT).timeout(TIMEOUT),
+            base_url: BASE_URL.into(),
+            protocol: CasProtocol::V3,
+            max_attempts: 3,
+            min_confidence: 0.0,
+            max_refreshes: 3,
+            #[cfg(feature = "validate-code-lite")]
+            solver: Some(Arc::new(TemplateSolver)),
+            #[cfg(not(feature = "validate-code-lite"))]
+            solver: None,
+            recorder: None,
+        }
+    }
+
+    /// Build the [`CasClient`].
+    ///
+    /// Returns an error of kind [`ErrorKind::TlsError`] if the TLS backend fails to load,
+    /// or of kind [`ErrorKind::BuilderError`] if the configuration is invalid, e.g. the
+    /// user agent is not a valid header value.
+    pub fn build(self) -> Result<CasClient, CasError> {
+        let client = self
+            .inner
+            .redirect(Policy::none())
+            .build()
+            .map_err(build_error)?;
+        Ok(CasClient {
+            client,
+            login_url: login_url(&self.base_url),
+            image_url: image_url(&self.base_url),
+            validate_url: validate_url(&self.base_url, self.protocol),
+            max_attempts: self.max_attempts,
+            min_confidence: self.min_confidence,
+            max_refreshes: self.max_refreshes,
+            solver: self.solver,
+            recorder: self.recorder,
+        })
+    }
+
+    /// Set the root url of the CAS server. Defaults to `https://passport.ustc.edu.cn`.
+    pub fn base_url<T: Into<String>>(mut self, url: T) -> Self {
+        self.base_url = url.into().trim_end_matches('/').into();
+        self
+    }
+
+    /// Set the protocol used by [`CasClient::validate_ticket`]. Defaults to
+    /// [`CasProtocol::V3`].
+    pub fn cas_protocol(mut self, protocol: CasProtocol) -> Self {
+        self.protocol = protocol;
+        self
+    }
+
+    /// Set how many times to try logging in when CAS rejects the validate code. Each try
+    /// starts over with a fresh login page and validate code. Defaults to 3.
+    ///
+    /// Other errors, such as a wrong password, are never retried. `0` is treated as `1`.
+    pub fn max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = attempts.max(1);
+        self
+    }
+
+    /// Fetch a new validate code, in the same CAS session, instead of submitting an answer
+    /// the captcha solver is less sure about than `confidence`, as reported by
+    /// [`CaptchaSolver::solve_with_confidence`]. An unsure answer is submitted anyway
+    /// once [`max_captcha_refreshes`](Self::max_captcha_refreshes) is used up, since only
+    /// the latest validate code of a session is valid.
+    ///
+    /// Refetching costs one request, while a rejected answer costs a whole login attempt
+    /// and counts towards account lockout. Defaults to `0.0`, never refetching.
+    pub fn min_captcha_confidence(mut self, confidence: f32) -> Self {
+        self.min_confidence = confidence;
+        self
+    }
+
+    /// Set how many times a validate code may be refetched for each login attempt, see
+    /// [`min_captcha_confidence`](Self::min_captcha_confidence). A validate code the
+    /// solver fails to read is refetched too, and the login fails only once the refreshes
+    /// are used up. Defaults to 3.
+    pub fn max_captcha_refreshes(mut self, refreshes: u32) -> Self {
+        self.max_refreshes = refreshes;
+        self
+    }
+
+    /// Set the solver recognizing validate code. Defaults to [`TemplateSolver`] if
+    /// `validate-code` or `validate-code-lite` feature is enabled, otherwise there is no
+    /// solver.
+    pub fn captcha_solver<T: AsyncCaptchaSolver + 'static>(mut self, solver: T) -> Self {
+        self.solver = Some(Arc::new(solver));
+        self
+    }
+
+    /// Set a recorder told about every validate code CAS accepts or rejects, such as a
+    /// [`CorpusRecorder`] collecting images for training. There is none by default.
+    pub fn captcha_recorder<T: CaptchaRecorder + 'static>(mut self, recorder: T) -> Self {
+        self.recorder = Some(Arc::new(recorder));
+        self
+    }
+
+    /// Set the `User-Agent` header. Defaults to a desktop browser.
+    pub fn user_agent<T: AsRef<str>>(mut self, value: T) -> Self {
+        self.inner = self.inner.user_agent(value.as_ref());
+        self
+    }
+
+    /// Set a timeout for each request, from connecting until the response body has finished.
+    /// Defaults to 30 seconds.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.inner = self.inner.timeout(timeout);
+        self
+    }
+
+    /// Set a timeout for only the connect phase of each request. Not set by default, so
+    /// only [`timeout`](Self::timeout) applies.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.inner = self.inner.connect_timeout(timeout);
+        self
+    }
+
+    /// Add a proxy to the list of proxies used.
+    pub fn proxy(mut self, proxy: Proxy) -> Self {
+        self.inner = self.inner.proxy(proxy);
+        self
+    }
+
+    /// Disable all proxies, including the system ones.
+    pub fn no_proxy(mut self) -> Self {
+        self.inner = self.inner.no_proxy();
+        self
+    }
+
+    /// Add a custom root certificate to trust.
+    #[cfg(any(feature = "native-tls", feature = "rustls-tls"))]
+    pub fn add_root_certificate(mut self, cert: reqwest::Certificate) -> Self {
+        self.inner = self.inner.add_root_certificate(cert);
+        self
+    }
+
+    /// Control whether invalid certificates are accepted. Dangerous, use with care.
+    #[cfg(any(feature = "native-tls", feature = "rustls-tls"))]
+    pub fn danger_accept_invalid_certs(mut self, accept: bool) -> Self {
+        self.inner = self.inner.danger_accept_invalid_certs(accept);
+        self
+    }
+}
+
+impl Debug for CasClient {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CasClient")
+            .field("client", &self.client)
+            .field("login_url", &self.login_url)
+            .field("image_url", &self.image_url)
+            .field("validate_url", &self.validate_url)
+            .field("max_attempts", &self.max_attempts)
+            .field("min_confidence", &self.min_confidence)
+            .field("max_refreshes", &self.max_refreshes)
+            .field("solver", &self.solver.as_ref().map(|_| ".."))
+            .field("recorder", &self.recorder.as_ref().map(|_| ".."))
+            .finish()
+    }
+}
+
+impl Debug for CasClientBuilder {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CasClientBuilder")
+            .field("inner", &self.inner)
+            .field("base_url", &self.base_url)
+            .field("protocol", &self.protocol)
+            .field("max_attempts", &self.max_attempts)
+            .field("min_confidence", &self.min_confidence)
+            .field("max_refreshes", &self.max_refreshes)
+            .field("solver", &self.solver.as_ref().map(|_| ".."))
+            .field("recorder", &self.recorder.as_ref().map(|_| ".."))
+            .finish()
+    }
+}
+
+impl Default for CasClientBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,24 +1,150 @@
-use reqwest::Error as RqError;
+use crate::SolverError;
+use reqwest::{Error as RqError, StatusCode};
 use std::error::Error;
 use std::fmt::{Display, Formatter};
 
 ///
 ///  The error kind used by `CasError`
 ///
-/// Use `match` to process different kind.
+/// Use `match` to process different kind. New kinds may be added in the future,
+/// so a wildcard arm is required.
 ///
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
 pub enum ErrorKind {
+    /// The username or password is wrong.
     UserInfoIncorrect,
+    /// The service url is not accepted by CAS.
     ServiceUrlIncorrect,
+    /// Connecting to the server or transferring data failed.
     NetworkError,
+    /// The server responded with a 4xx or 5xx status code.
+    HttpStatusError,
+    /// The response body or headers could not be decoded.
+    InvalidResponse,
+    /// The validate code image could not be decoded or recognized.
+    ValidateCodeError,
+    /// The validate code submitted was rejected by CAS.
+    ValidateCodeIncorrect,
+    /// Validate code is requested but there is no captcha solver to recognize it.
+    CaptchaRequired,
+    /// The account is locked, usually after too many failed attempts.
+    AccountLocked,
+    /// CAS is under maintenance or temporarily unavailable.
+    ServiceUnavailable,
+    /// The page returned by CAS has an unexpected layout, the CAS interface has
+    /// probably changed.
+    InterfaceChanged,
+    /// The request timed out.
+    Timeout,
+    /// Setting up a TLS connection failed, e.g. the certificate is not trusted, or the TLS
+    /// backend could not be initialized.
+    ///
+    /// The HTTP client does not tell TLS errors apart from other connection errors, so
+    /// they are recognized by the messages of the underlying errors. This is a heuristic,
+    /// an unusual TLS error may be reported as [`NetworkError`](ErrorKind::NetworkError),
+    /// and the other way around.
+    TlsError,
+    /// The ticket was rejected when validating it, e.g. it has expired or has been used
+    /// already.
+    TicketInvalid,
+    /// The client or a request could not be built from its configuration, e.g. the user
+    /// agent is not a valid header value, or the base URL is not a valid URL.
+    BuilderError,
+}
+
+impl ErrorKind {
+    ///
+    /// Whether the operation may succeed if simply tried again later.
+    ///
+    /// Errors caused by the network, an overloaded server or a misrecognized validate code
+    /// are retryable. Errors caused by wrong user input, a locked account or an incompatible
+    /// CAS interface are not.
+    ///
+    /// [`HttpStatusError`](ErrorKind::HttpStatusError) is not, as it depends on the status,
+    /// see [`CasError::is_retryable`].
+    ///
+    pub fn is_retryable(&self) -> bool {
+        use ErrorKind::*;
+        matches!(
+            self,
+            NetworkError | ValidateCodeIncorrect | ServiceUnavailable | Timeout
+        )
+    }
+
+    /// Whether the error is caused by the username or password.
+    pub fn is_credential_error(&self) -> bool {
+        matches!(
+            self,
+            ErrorKind::UserInfoIncorrect | ErrorKind::AccountLocked
+        )
+    }
+
+    /// Whether the error happened in the transport layer, before any CAS page was received.
+    pub fn is_transport_error(&self) -> bool {
+        use ErrorKind::*;
+        matches!(self, NetworkError | Timeout | TlsError)
+    }
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        use ErrorKind::*;
+        match self {
+            UserInfoIncorrect => {
+                write!(f, "Username or password incorrect")
+            }
+            ServiceUrlIncorrect => {
+                write!(f, "Service url incorrect")
+            }
+            NetworkError => {
+                write!(f, "Network failed")
+            }
+            HttpStatusError => {
+                write!(f, "Server returned an error status")
+            }
+            InvalidResponse => {
+                write!(f, "Server returned an invalid response")
+            }
+            ValidateCodeError => {
+                write!(f, "Validate code recognition failed")
+            }
+            ValidateCodeIncorrect => {
+                write!(f, "Validate code incorrect")
+            }
+            CaptchaRequired => {
+                write!(f, "Validate code required but no captcha solver available")
+            }
+            AccountLocked => {
+                write!(f, "Account locked")
+            }
+            ServiceUnavailable => {
+                write!(f, "CAS is under maintenance or unavailable")
+            }
+            InterfaceChanged => {
+                write!(f, "Unexpected CAS page, the interface may have changed")
+            }
+            Timeout => {
+                write!(f, "Request timed out")
+            }
+            TlsError => {
+                write!(f, "TLS failed")
+            }
+            TicketInvalid => {
+                write!(f, "Ticket invalid")
+            }
+            BuilderError => {
+                write!(f, "Client configuration invalid")
+            }
+        }
+    }
 }
 
 ///
 /// The error type.
 ///
-/// There are only three kinds of errors,
-/// use `kind()` method to get `ErrorKind`.
+/// use `kind()` method to get `ErrorKind`, and `message()` method to get the
+/// error message shown by CAS.
 ///
 /// Use `get_ref()`, `get_mut()`, `into_inner()`, or `source()` method to get
 /// the underlying error.
@@ -26,6 +152,8 @@ pub enum ErrorKind {
 #[derive(Debug)]
 pub struct CasError {
     kind: ErrorKind,
+    message: Option<String>,
+    attempts: Option<u32>,
     inner: Option<Box<dyn Error + Send + Sync + 'static>>,
 }
 
@@ -36,12 +164,46 @@ impl CasError {
     {
         Self {
             kind,
+            message: None,
+            attempts: None,
             inner: Some(Box::new(inner)),
         }
     }
 
     pub(crate) fn new(kind: ErrorKind) -> Self {
-        Self { kind, inner: None }
+        Self {
+            kind,
+            message: None,
+            attempts: None,
+            inner: None,
+        }
+    }
+
+    pub(crate) fn with_message<M: Into<String>>(kind: ErrorKind, message: M) -> Self {
+        Self {
+            kind,
+            message: Some(message.into()),
+            attempts: None,
+            inner: None,
+        }
+    }
+
+    /// wrap an error returned by a captcha solver, keeping `CasError`s as they are.
+    pub(crate) fn from_solver(err: SolverError) -> Self {
+        match err.downcast::<CasError>() {
+            Ok(err) => *err,
+            Err(err) => Self {
+                kind: ErrorKind::ValidateCodeError,
+                message: None,
+                attempts: None,
+                inner: Some(err),
+            },
+        }
+    }
+
+    pub(crate) fn with_attempts(mut self, attempts: u32) -> Self {
+        self.attempts = Some(attempts);
+        self
     }
 
     pub fn get_ref(&self) -> Option<&(dyn Error + Send + Sync + 'static)> {
@@ -59,21 +221,46 @@ impl CasError {
     pub fn kind(&self) -> ErrorKind {
         self.kind
     }
+
+    /// The error message shown by CAS, as is, if there is one.
+    ///
+    /// For example `"用户名或密码错误"` when the password is wrong.
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+
+    /// The number of login attempts made before giving up, if the error comes from
+    /// a login. The kind and message describe the last attempt.
+    pub fn attempts(&self) -> Option<u32> {
+        self.attempts
+    }
+
+    /// Whether the operation may succeed if simply tried again later, see
+    /// [`ErrorKind::is_retryable`]. An [`ErrorKind::HttpStatusError`] is retryable if the
+    /// status is a server error (5xx) or 429 Too Many Requests.
+    pub fn is_retryable(&self) -> bool {
+        self.kind.is_retryable()
+            || self.kind == ErrorKind::HttpStatusError && self.retryable_status()
+    }
+
+    fn retryable_status(&self) -> bool {
+        let status = self
+            .get_ref()
+            .and_then(|e| e.downcast_ref::<RqError>())
+            .and_then(RqError::status);
+        matches!(status, Some(s) if s.is_server_error() || s == StatusCode::TOO_MANY_REQUESTS)
+    }
 }
 
 impl Display for CasError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        use ErrorKind::*;
-        match self.kind {
-            UserInfoIncorrect => {
-                write!(f, "Username or password incorrect")
-            }
-            ServiceUrlIncorrect => {
-                write!(f, "Service url incorrect")
-            }
-            NetworkError => {
-                write!(f, "Network failed")
-            }
+        match &self.message {
+            Some(message) => write!(f, "{}: {message}", self.kind)?,
+            None => self.kind.fmt(f)?,
+        }
+        match self.attempts {
+            Some(attempts) if attempts > 1 => write!(f, " (after {attempts} attempts)"),
+            _ => Ok(()),
         }
     }
 }
@@ -86,6 +273,51 @@ impl Error for CasError {
 
 impl From<RqError> for CasError {
     fn from(value: RqError) -> Self {
-        Self::with_source(ErrorKind::NetworkError, value)
+        let kind = if value.is_builder() {
+            ErrorKind::BuilderError
+        } else if value.is_timeout() {
+            ErrorKind::Timeout
+        } else if value.status() == Some(StatusCode::SERVICE_UNAVAILABLE) {
+            ErrorKind::ServiceUnavailable
+        } else if value.is_status() {
+            ErrorKind::HttpStatusError
+        } else if value.is_decode() {
+            ErrorKind::InvalidResponse
+        } else if is_tls_error(&value) {
+            ErrorKind::TlsError
+        } else {
+            ErrorKind::NetworkError
+        };
+        Self::with_source(kind, value)
+    }
+}
+
+/// The error of building the HTTP client, a [`ErrorKind::TlsError`] if the TLS backend
+/// failed, or a [`ErrorKind::BuilderError`] otherwise.
+pub(crate) fn build_error(err: RqError) -> CasError {
+    let kind = if is_tls_error(&err) {
+        ErrorKind::TlsError
+    } else {
+        ErrorKind::BuilderError
+    };
+    CasError::with_source(kind, err)
+}
+
+/// reqwest does not tell tls errors apart, so look for them in the source chain.
+///
+/// This is a heuristic matching the messages of the underlying errors, which works for
+/// the errors of native-tls and rustls, but may misclassify an error merely mentioning
+/// one of the keywords.
+fn is_tls_error(err: &RqError) -> bool {
+    const KEYWORDS: [&str; 4] = ["certificate", "tls", "ssl", "handshake"];
+
+    let mut source = err.source();
+    while let Some(e) = source {
+        let msg = e.to_string().to_ascii_lowercase();
+        if KEYWORDS.iter().any(|k| msg.contains(k)) {
+            return true;
+        }
+        source = e.source();
     }
+    false
 }
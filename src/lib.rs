@@ -5,6 +5,12 @@
 //! be found from browser's address bar when logging into CAS by hand. The returned ticket value
 //! can be used for further authentication specific to websites.
 //!
+//! If timeouts, proxies or other settings are needed, build a [`CasClient`] with
+//! [`CasClientBuilder`] and call [`CasClient::get_ticket`] instead.
+//!
+//! Services receiving a ticket validate it with [`validate_ticket`], which asks CAS who
+//! the ticket was issued to.
+//!
 //! [`ustc_cas::get_ticket`](get_ticket) is an async function and requires a async runtime
 //! to execute. While [`ustc_cas::blocking::get_ticket`](blocking::get_ticket),
 //! enabled by `blocking` feature, can not be used in an aysnc runtime.
@@ -37,10 +43,28 @@
 //! ```
 //!
 //! # Features
-//! - `validate-code`: Validate code recognition using `image` and `bytes` crate.
-//! `get_ticket` function will panic if this feature is disabled but validate code is requested.
-//! Enabled by default.
-//! - `blocking`: provide blocking version of `get_ticket` function.
+//! - `validate-code`: Validate code recognition using `image` crate, provided as
+//!   [`TemplateSolver`]. Enabled by default. If this feature is disabled, either set a
+//!   custom [`CaptchaSolver`] with [`CasClientBuilder::captcha_solver`], or solve the
+//!   validate code yourself with [`CasClient::start_login`]. Otherwise `get_ticket` returns
+//!   [`ErrorKind::CaptchaRequired`] when validate code is requested. [`TerminalSolver`],
+//!   asking the user to type the code, and the standalone recognizer in [`captcha`] module
+//!   are also provided by this feature. Only JPEG validate codes are decoded.
+//! - `validate-code-lite`: Everything of `validate-code` without `image` crate, decoding
+//!   JPEG validate codes with a small built-in decoder instead, for faster builds and
+//!   smaller binaries. See [`captcha::LumaImage::decode_jpeg`].
+//! - `png`: decode PNG validate codes too. Implies `validate-code`.
+//! - `gif`: decode GIF validate codes too. Implies `validate-code`.
+//! - `cnn`: A small convolutional network classifying validate code digits, as an
+//!   alternative to templates, provided as [`CnnSolver`] and
+//!   [`captcha::CnnModel`]. Implies `validate-code-lite`.
+//! - `blocking`: provide blocking version of `get_ticket` and `validate_ticket` functions
+//!   and `CasClient`.
+//! - `train`: build `ustc-cas-captcha-train` binary, making captcha templates from labeled
+//!   images with [`captcha::TemplateTrainer`]. Implies `validate-code`.
+//! - `eval`: build `ustc-cas-captcha-eval` binary, measuring the accuracy of the captcha
+//!   recognizer over labeled images with [`captcha::Evaluation`]. Implies `validate-code`.
+//! - `serde`: Serialize and deserialize [`Principal`] with `serde` crate.
 //! - `native-tls`: Use system tls library. Enabled by default.
 //! - `rustls-tls`: Use rustls for tls functionality.
 //!
@@ -48,28 +72,48 @@
 
 #[cfg(feature = "blocking")]
 pub mod blocking;
+#[cfg(feature = "validate-code-lite")]
+pub mod captcha;
+mod challenge;
+mod client;
 mod error;
-#[cfg(feature = "validate-code")]
-mod validate_code;
+mod principal;
+mod recorder;
+mod solver;
+#[cfg(feature = "validate-code-lite")]
+mod terminal;
+mod validate;
 
+pub use challenge::*;
+pub use client::*;
 pub use error::*;
+pub use principal::*;
+pub use recorder::*;
+#[cfg(any(feature = "native-tls", feature = "rustls-tls"))]
+pub use reqwest::Certificate;
+pub use reqwest::Proxy;
+pub use solver::*;
+#[cfg(feature = "validate-code-lite")]
+pub use terminal::*;
+pub use validate::CasProtocol;
 
-use once_cell::sync::Lazy;
+use once_cell::sync::{Lazy, OnceCell};
 use regex::Regex;
-use reqwest::header::HeaderMap;
-use reqwest::{redirect::Policy, Client};
+use reqwest::cookie::{CookieStore, Jar};
+use reqwest::header::{HeaderMap, COOKIE, LOCATION, SET_COOKIE};
+use reqwest::{redirect::Policy, Client, RequestBuilder, Response};
 use std::collections::HashMap;
+use std::sync::Arc;
+use validate::{parse_validation, validate_url};
 
 ///
 /// log into USTC CAS System and get ticket value.
 ///
-/// # Panics
-///
-/// The function will panic if `validate-code` feature is disabled but validate code recognition
-/// is needed.
+/// This is a shortcut for [`CasClient::get_ticket`] on a default [`CasClient`] shared
+/// by the whole process. Build your own [`CasClient`] if any configuration is needed.
 ///
-/// The function may panic if the CAS interface changed. This kind of panic is considered
-/// a bug and needs to be fixed.
+/// If neither `validate-code` nor `validate-code-lite` feature is enabled but validate
+/// code is requested, an error of kind [`ErrorKind::CaptchaRequired`] is returned.
 ///
 pub async fn get_ticket<U, P, S>(
     username: U,
@@ -81,83 +125,146 @@ where
     P: AsRef<str>,
     S: AsRef<str>,
 {
-    static CLIENT: Lazy<Client> = Lazy::new(|| {
-        Client::builder()
-            .user_agent(USER_AGENT)
-            .cookie_store(true)
-            .redirect(Policy::none())
-            .build()
-            .unwrap()
-    });
-
-    let username = username.as_ref();
-    let password = password.as_ref();
-    let service_url = service_url.as_ref();
-
-    let rsps = CLIENT
-        .get(format!("{URL}?service={service_url}"))
-        .send()
-        .await?
-        .error_for_status()
-        .unwrap();
-
-    let text = rsps.text().await.unwrap();
-    let cas_lt = get_cas_lt(&text)?.into();
-    let mut form = get_form(text)?;
-    form.insert("username".into(), username.into());
-    form.insert("password".into(), password.into());
-    form.insert("CAS_LT".into(), cas_lt);
-
-    #[cfg(feature = "validate-code")]
-    if form["showCode"] == "1" {
-        let rsps = CLIENT
-            .get(IMAGE_URL)
-            .send()
-            .await?
-            .error_for_status()
-            .unwrap();
-        let code = validate_code::get_validatecode(rsps.bytes().await.unwrap());
-        form.insert("LT".into(), code);
-    }
-
-    #[cfg(not(feature = "validate-code"))]
-    if form["showCode"] == "1" {
-        panic!("validate code needed but validate-code feature not enabled");
-    }
+    default_client()?
+        .get_ticket(username, password, service_url)
+        .await
+}
 
-    form.insert("button".into(), "".into());
+///
+/// validate a service ticket and get the user it was issued to.
+///
+/// This is a shortcut for [`CasClient::validate_ticket`] on the default [`CasClient`]
+/// shared by the whole process, validating with CAS 3.0.
+///
+/// # Example
+/// ```rust
+/// # async fn run(ticket: &str) -> Result<(), ustc_cas::CasError> {
+/// let principal =
+///     ustc_cas::validate_ticket(ticket, "https://jw.ustc.edu.cn/ucas-sso/login").await?;
+/// println!("user: {}", principal.user());
+/// # Ok(())
+/// # }
+/// ```
+///
+pub async fn validate_ticket<T, S>(ticket: T, service_url: S) -> Result<Principal, CasError>
+where
+    T: AsRef<str>,
+    S: AsRef<str>,
+{
+    default_client()?.validate_ticket(ticket, service_url).await
+}
 
-    let rsps = CLIENT
-        .post(URL)
-        .form(&form)
-        .send()
-        .await?
-        .error_for_status()
-        .unwrap();
+fn default_client() -> Result<&'static CasClient, CasError> {
+    static CLIENT: OnceCell<CasClient> = OnceCell::new();
 
-    match_ticket(rsps.headers())
+    CLIENT.get_or_try_init(|| CasClient::builder().build())
 }
 
-const URL: &str = "https://passport.ustc.edu.cn/login";
-const IMAGE_URL: &str = "https://passport.ustc.edu.cn/validatecode.jsp?type=login";
+const BASE_URL: &str = "https://passport.ustc.edu.cn";
+const TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
 const USER_AGENT: &str = "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 \
             (KHTML, like Gecko) Chrome/103.0.5060.134 Safari/537.36 Edg/103.0.1264.77";
 static TICKET_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r#"ticket=(\S*)"#).unwrap());
 
+fn login_url(base_url: &str) -> String {
+    format!("{base_url}/login")
+}
+
+fn image_url(base_url: &str) -> String {
+    format!("{base_url}/validatecode.jsp?type=login")
+}
+
 fn match_ticket(headers: &HeaderMap) -> Result<String, CasError> {
+    let location = headers
+        .get(LOCATION)
+        .ok_or(CasError::new(ErrorKind::UserInfoIncorrect))?
+        .to_str()
+        .map_err(|e| CasError::with_source(ErrorKind::InvalidResponse, e))?;
     let ticket = &TICKET_RE
-        .captures_iter(
-            headers
-                .get("location")
-                .ok_or(CasError::new(ErrorKind::UserInfoIncorrect))?
-                .to_str()
-                .unwrap(),
-        )
+        .captures_iter(location)
         .next()
         .ok_or(CasError::new(ErrorKind::ServiceUrlIncorrect))?[1];
     Ok(ticket.into())
 }
 
+/// Build the error for a rejected login from the page CAS responded with.
+///
+/// The message shown on the page is attached to the error as is. Falls back to
+/// `UserInfoIncorrect` if no message can be found.
+fn login_error(page: &str) -> CasError {
+    static MSG_RE: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(
+            r#"<(?:div|span|p|font|label)[^>]*?(?:id|class)="[^"]*?(?:err|msg|alert)[^"]*?"[^>]*>([\s\S]*?)</(?:div|span|p|font|label)>"#,
+        )
+        .unwrap()
+    });
+    static ALERT_RE: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r#"alert\(\s*["']([^"']+)["']\s*\)"#).unwrap());
+
+    let message = MSG_RE
+        .captures_iter(page)
+        .map(|cap| strip_html(&cap[1]))
+        .chain(ALERT_RE.captures_iter(page).map(|cap| strip_html(&cap[1])))
+        .find(|msg| !msg.is_empty());
+
+    match message {
+        Some(message) => CasError::with_message(login_error_kind(&message), message),
+        None => CasError::new(ErrorKind::UserInfoIncorrect),
+    }
+}
+
+fn login_error_kind(message: &str) -> ErrorKind {
+    const LOCKED: [&str; 5] = ["锁定", "冻结", "禁用", "次数过多", "频繁"];
+    const UNAVAILABLE: [&str; 2] = ["维护", "繁忙"];
+
+    if message.contains("验证码") {
+        ErrorKind::ValidateCodeIncorrect
+    } else if LOCKED.iter().any(|k| message.contains(k)) {
+        ErrorKind::AccountLocked
+    } else if UNAVAILABLE.iter().any(|k| message.contains(k)) {
+        ErrorKind::ServiceUnavailable
+    } else {
+        ErrorKind::UserInfoIncorrect
+    }
+}
+
+/// Remove tags, decode the common entities and collapse whitespaces.
+fn strip_html(html: &str) -> String {
+    static TAG_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"<[^>]*>").unwrap());
+
+    let text = TAG_RE
+        .replace_all(html, " ")
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&");
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Tell `recorder` whether CAS accepted `code`, read as `text`, if the outcome of `result`
+/// says so.
+fn record_captcha(
+    recorder: &Option<Arc<dyn CaptchaRecorder>>,
+    image: &[u8],
+    text: &str,
+    code: &str,
+    result: &Result<String, CasError>,
+) {
+    let accepted = match result {
+        Ok(_) => true,
+        Err(e) if e.kind() == ErrorKind::ValidateCodeIncorrect => false,
+        Err(_) => return,
+    };
+    if let Some(recorder) = recorder {
+        recorder.record(image, text, code, accepted);
+    }
+}
+
+fn need_validate_code(form: &HashMap<String, String>) -> bool {
+    form.get("showCode").map_or(false, |v| v == "1")
+}
+
 fn get_form(data: String) -> Result<HashMap<String, String>, CasError> {
     static RE: Lazy<Regex> = Lazy::new(|| {
         Regex::new(r#"<input type="hidden"[\s\S]*?name="(\S*?)" value="(\S*?)""#).unwrap()
@@ -175,10 +282,16 @@ fn get_form(data: String) -> Result<HashMap<String, String>, CasError> {
 }
 
 fn get_cas_lt(data: &str) -> Result<&str, CasError> {
-    static RE: Lazy<Regex> = Lazy::new(|| {
-        Regex::new(r##"\$\("#CAS_LT"\).val\("(\S*?)"\);"##).unwrap()
-    });
-    let cap = RE.captures(data).ok_or(CasError::new(ErrorKind::NetworkError))?;
-    let a = cap.get(1).ok_or(CasError::new(ErrorKind::NetworkError))?.as_str();
-    Ok(a)
+    static RE: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r##"\$\("#CAS_LT"\).val\("(\S*?)"\);"##).unwrap());
+    match RE.captures(data).and_then(|cap| cap.get(1)) {
+        Some(m) => Ok(m.as_str()),
+        None if is_maintenance_page(data) => Err(CasError::new(ErrorKind::ServiceUnavailable)),
+        None => Err(CasError::new(ErrorKind::InterfaceChanged)),
+    }
+}
+
+fn is_maintenance_page(data: &str) -> bool {
+    const KEYWORDS: [&str; 4] = ["系统维护", "维护中", "暂停服务", "升级中"];
+    KEYWORDS.iter().any(|k| data.contains(k))
 }
@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+
+///
+/// The user a service ticket was issued to, as returned by
+/// [`CasClient::validate_ticket`](crate::CasClient::validate_ticket).
+///
+/// Besides the user id, CAS 3.0 releases attributes of the user to the service. The
+/// attributes USTC CAS is known to release have typed accessors, such as
+/// [`name`](Self::name) and [`student_number`](Self::student_number), which return `None`
+/// if the attribute is missing, e.g. when validating with CAS 2.0. Every attribute,
+/// known or not, is kept in [`attributes`](Self::attributes). An attribute may have
+/// several values, in the order CAS sent them.
+///
+/// With `serde` feature, a principal can be serialized and deserialized, for keeping it
+/// in the session store of a service.
+///
+/// # Example
+/// ```rust
+/// # async fn run(ticket: &str) -> Result<(), ustc_cas::CasError> {
+/// let service = "https://jw.ustc.edu.cn/ucas-sso/login";
+/// let principal = ustc_cas::validate_ticket(ticket, service).await?;
+/// println!(
+///     "{} ({}) of {}",
+///     principal.name().unwrap_or("unknown"),
+///     principal.student_number().unwrap_or(principal.user()),
+///     principal.department_code().unwrap_or("unknown department"),
+/// );
+/// # Ok(())
+/// # }
+/// ```
+///
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Principal {
+    user: String,
+    #[cfg_attr(feature = "serde", serde(default))]
+    attributes: HashMap<String, Vec<String>>,
+}
+
+impl Principal {
+    /// Create a principal of `user` with `attributes`, e.g. for testing a service.
+    pub fn new<U: Into<String>>(user: U, attributes: HashMap<String, Vec<String>>) -> Self {
+        Self {
+            user: user.into(),
+            attributes,
+        }
+    }
+
+    /// The user id the ticket was issued to.
+    pub fn user(&self) -> &str {
+        &self.user
+    }
+
+    /// Every attribute released by CAS, by name.
+    pub fn attributes(&self) -> &HashMap<String, Vec<String>> {
+        &self.attributes
+    }
+
+    /// The first value of attribute `name`, if CAS released it.
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attribute_values(name).first().map(|s| s.as_str())
+    }
+
+    /// Every value of attribute `name`, empty if CAS did not release it.
+    pub fn attribute_values(&self, name: &str) -> &[String] {
+        self.attributes.get(name).map_or(&[], |v| v.as_slice())
+    }
+
+    /// The first non-empty value among attributes `names`.
+    fn known(&self, names: &[&str]) -> Option<&str> {
+        names
+            .iter()
+            .filter_map(|name| self.attribute(name))
+            .find(|value| !value.is_empty())
+    }
+
+    /// The global id of the user, a number which stays the same when the student number
+    /// changes, e.g. from a bachelor to a master student. Attribute `gid`.
+    pub fn gid(&self) -> Option<&str> {
+        self.known(&["gid"])
+    }
+
+    /// The student number, or the staff number of staff, such as `PB00000000`. Attribute
+    /// `zjhm`.
+    pub fn student_number(&self) -> Option<&str> {
+        self.known(&["zjhm"])
+    }
+
+    /// The real name of the user. Attribute `name`, or `xm`.
+    pub fn name(&self) -> Option<&str> {
+        self.known(&["name", "xm"])
+    }
+
+    /// The code of the department of the user. Attribute `deptCode`.
+    pub fn department_code(&self) -> Option<&str> {
+        self.known(&["deptCode"])
+    }
+
+    /// The name of the department of the user. Attribute `deptName`.
+    pub fn department(&self) -> Option<&str> {
+        self.known(&["deptName"])
+    }
+
+    /// The first email address of the user. Attribute `email`.
+    pub fn email(&self) -> Option<&str> {
+        self.known(&["email"])
+    }
+
+    /// Every email address of the user. Attribute `email`.
+    pub fn emails(&self) -> &[String] {
+        self.attribute_values("email")
+    }
+
+    /// The gender of the user. Attribute `xbm`, a code of GB/T 2261.1.
+    pub fn gender(&self) -> Option<Gender> {
+        match self.known(&["xbm"])? {
+            "1" => Some(Gender::Male),
+            "2" => Some(Gender::Female),
+            _ => None,
+        }
+    }
+
+    /// Whether the ticket comes from a login typing the password, rather than from an
+    /// existing CAS session. Attribute `isFromNewLogin` of CAS 3.0.
+    pub fn is_from_new_login(&self) -> Option<bool> {
+        match self.known(&["isFromNewLogin"])? {
+            "true" => Some(true),
+            "false" => Some(false),
+            _ => None,
+        }
+    }
+}
+
+///
+/// The gender of a [`Principal`].
+///
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum Gender {
+    /// Code `1`.
+    Male,
+    /// Code `2`.
+    Female,
+}
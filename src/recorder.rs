@@ -0,0 +1,156 @@
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+///
+/// Watch the validate codes submitted during login, and whether CAS accepted them.
+///
+/// A recorder set with [`CasClientBuilder::captcha_recorder`](crate::CasClientBuilder::captcha_recorder)
+/// is told about every validate code CAS has judged, whether it came from the captcha
+/// solver or from [`submit_captcha`](crate::CasClient::submit_captcha). A code counts as
+/// rejected when CAS responds with [`ErrorKind::ValidateCodeIncorrect`](crate::ErrorKind::ValidateCodeIncorrect).
+/// Codes of logins failing for other reasons, such as a wrong password, are not recorded,
+/// since whether the code was right is unknown.
+///
+/// Closures taking `(&[u8], &str, &str, bool)` implement this trait too.
+///
+/// The recorder is called on the thread running the login, so it should return quickly.
+///
+pub trait CaptchaRecorder: Send + Sync {
+    /// Record that `code` was submitted for `image`, and whether CAS accepted it.
+    ///
+    /// `text` is what the solver read from `image`, see [`Solution`](crate::Solution).
+    /// It is the code itself, unless the code is computed from the text, such as `8` for
+    /// `3+5=?`. For codes given to [`submit_captcha`](crate::CasClient::submit_captcha),
+    /// it is the code too.
+    fn record(&self, image: &[u8], text: &str, code: &str, accepted: bool);
+}
+
+impl<F> CaptchaRecorder for F
+where
+    F: Fn(&[u8], &str, &str, bool) + Send + Sync,
+{
+    fn record(&self, image: &[u8], text: &str, code: &str, accepted: bool) {
+        self(image, text, code, accepted)
+    }
+}
+
+///
+/// A [`CaptchaRecorder`] collecting a labeled corpus of validate code images.
+///
+/// Accepted codes are saved into the corpus directory as `<label>_<id>.<ext>`, the naming
+/// read by the `ustc-cas-captcha-train` and `ustc-cas-captcha-eval` tools. The label is the
+/// text read from the image, see [`label`](CorpusRecorder::label). Rejected codes
+/// are saved the same way into the `failed` subdirectory, where the tools do not look,
+/// for checking by hand. Directories are created as needed.
+///
+/// Errors while saving are ignored by [`record`](CaptchaRecorder::record), so a full disk
+/// never fails a login. Use [`save`](CorpusRecorder::save) to handle them.
+///
+/// # Example
+/// ```rust
+/// use ustc_cas::{CasClient, CorpusRecorder};
+///
+/// let client = CasClient::builder()
+///     .captcha_recorder(CorpusRecorder::new("captcha-corpus"))
+///     .build();
+/// ```
+///
+#[derive(Debug)]
+pub struct CorpusRecorder {
+    dir: PathBuf,
+    count: AtomicU64,
+}
+
+impl CorpusRecorder {
+    /// Create a recorder saving into `dir`.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self {
+            dir: dir.into(),
+            count: AtomicU64::new(0),
+        }
+    }
+
+    /// The corpus directory.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Save `image` labeled with `text`, and return the path of the file.
+    pub fn save(&self, image: &[u8], text: &str, accepted: bool) -> io::Result<PathBuf> {
+        let dir = if accepted {
+            self.dir.clone()
+        } else {
+            self.dir.join("failed")
+        };
+        std::fs::create_dir_all(&dir)?;
+
+        let label = Self::label(text);
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis());
+        let count = self.count.fetch_add(1, Ordering::Relaxed);
+        let path = dir.join(format!(
+            "{label}_{millis}-{}-{count}.{}",
+            std::process::id(),
+            extension(image)
+        ));
+        std::fs::write(&path, image)?;
+        Ok(path)
+    }
+
+    /// The file name label of `text`, where characters other than ASCII letters and digits
+    /// are percent-encoded in UTF-8, such as `3%2B5%3D%3F` for `3+5=?`.
+    pub fn label(text: &str) -> String {
+        let mut label = String::with_capacity(text.len());
+        for &b in text.as_bytes() {
+            if b.is_ascii_alphanumeric() {
+                label.push(b as char);
+            } else {
+                label += &format!("%{b:02X}");
+            }
+        }
+        label
+    }
+
+    /// The text of a file name label, the reverse of [`label`](CorpusRecorder::label).
+    /// Returns `None` if `label` is not a valid label.
+    pub fn text(label: &str) -> Option<String> {
+        let mut bytes = Vec::with_capacity(label.len());
+        let mut rest = label.as_bytes();
+        while let Some((&b, tail)) = rest.split_first() {
+            if b == b'%' {
+                let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+                bytes.push(u8::from_str_radix(hex, 16).ok()?);
+                rest = &tail[2..];
+            } else if b.is_ascii_alphanumeric() {
+                bytes.push(b);
+                rest = tail;
+            } else {
+                return None;
+            }
+        }
+        String::from_utf8(bytes).ok()
+    }
+}
+
+impl CaptchaRecorder for CorpusRecorder {
+    /// Save `image` labeled with `text`, ignoring errors.
+    fn record(&self, image: &[u8], text: &str, _code: &str, accepted: bool) {
+        let _ = self.save(image, text, accepted);
+    }
+}
+
+/// Guess the file extension from the magic number.
+fn extension(image: &[u8]) -> &'static str {
+    if image.starts_with(&[0xff, 0xd8, 0xff]) {
+        "jpg"
+    } else if image.starts_with(b"\x89PNG") {
+        "png"
+    } else if image.starts_with(b"GIF8") {
+        "gif"
+    } else {
+        "bin"
+    }
+}
@@ -0,0 +1,253 @@
+#[cfg(feature = "validate-code-lite")]
+use crate::{CasError, ErrorKind};
+use std::error::Error;
+use std::future::Future;
+use std::pin::Pin;
+
+/// The error type returned by captcha solvers.
+pub type SolverError = Box<dyn Error + Send + Sync + 'static>;
+
+/// The future returned by [`AsyncCaptchaSolver::solve`],
+/// [`AsyncCaptchaSolver::solve_with_confidence`] and [`AsyncCaptchaSolver::solve_with_text`].
+pub type SolveFuture<'a, T = String> =
+    Pin<Box<dyn Future<Output = Result<T, SolverError>> + Send + 'a>>;
+
+///
+/// Recognize the validate code (captcha) shown on the login page.
+///
+/// The solver receives the raw image bytes of `validatecode.jsp`, usually a JPEG file,
+/// and returns the code to submit. When the solver returns an error, a new validate code
+/// is fetched, up to [`CasClientBuilder::max_captcha_refreshes`](crate::CasClientBuilder::max_captcha_refreshes)
+/// times. The last error is then reported as
+/// [`ErrorKind::ValidateCodeError`](crate::ErrorKind::ValidateCodeError) with the
+/// original error as source.
+///
+/// Closures taking `&[u8]` implement this trait too.
+///
+/// # Example
+/// ```rust
+/// use ustc_cas::{CaptchaSolver, CasClient, SolverError};
+///
+/// struct AlwaysZero;
+///
+/// impl CaptchaSolver for AlwaysZero {
+///     fn solve(&self, _image: &[u8]) -> Result<String, SolverError> {
+///         Ok("0000".into())
+///     }
+/// }
+///
+/// let client = CasClient::builder().captcha_solver(AlwaysZero).build();
+/// ```
+///
+pub trait CaptchaSolver: Send + Sync {
+    /// Recognize the code in `image`.
+    fn solve(&self, image: &[u8]) -> Result<String, SolverError>;
+
+    /// Recognize the code in `image`, along with how sure the solver is about it, from
+    /// `0.0` to `1.0`. Logins fetch a new validate code instead of submitting an unsure
+    /// answer, see [`CasClientBuilder::min_captcha_confidence`](crate::CasClientBuilder::min_captcha_confidence).
+    ///
+    /// Defaults to [`solve`](Self::solve) with a confidence of `1.0`.
+    fn solve_with_confidence(&self, image: &[u8]) -> Result<(String, f32), SolverError> {
+        self.solve(image).map(|code| (code, 1.0))
+    }
+
+    /// Recognize the code in `image`, along with the characters read from it, which the
+    /// [`CaptchaRecorder`](crate::CaptchaRecorder) is told about. They differ from the
+    /// code for an arithmetic code such as `3+5=?`, whose code is the value.
+    ///
+    /// Defaults to [`solve_with_confidence`](Self::solve_with_confidence), reading the code
+    /// itself.
+    fn solve_with_text(&self, image: &[u8]) -> Result<Solution, SolverError> {
+        self.solve_with_confidence(image)
+            .map(|(code, confidence)| Solution::new(code, confidence))
+    }
+}
+
+///
+/// A validate code recognized by [`CaptchaSolver::solve_with_text`].
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Solution {
+    /// The code to submit.
+    pub code: String,
+    /// The characters read from the image, such as `3+5=?` when the code is `8`.
+    pub text: String,
+    /// How sure the solver is about the code, from `0.0` to `1.0`.
+    pub confidence: f32,
+}
+
+impl Solution {
+    /// A solution whose text is the code itself.
+    pub fn new(code: String, confidence: f32) -> Self {
+        Self {
+            text: code.clone(),
+            code,
+            confidence,
+        }
+    }
+}
+
+///
+/// Async version of [`CaptchaSolver`], for solvers doing I/O such as a remote
+/// recognition service.
+///
+/// Every [`CaptchaSolver`] is an `AsyncCaptchaSolver` as well, solving the code in place.
+/// Solvers that block for a long time should implement this trait directly and move the
+/// work off the async runtime.
+///
+/// # Example
+/// ```rust
+/// use ustc_cas::{AsyncCaptchaSolver, CasClient, SolveFuture};
+///
+/// struct Remote;
+///
+/// impl AsyncCaptchaSolver for Remote {
+///     fn solve<'a>(&'a self, image: &'a [u8]) -> SolveFuture<'a> {
+///         Box::pin(async move {
+///             // send `image` to the recognition service here
+///             Ok(format!("{:04}", image.len() % 10000))
+///         })
+///     }
+/// }
+///
+/// let client = CasClient::builder().captcha_solver(Remote).build();
+/// ```
+///
+pub trait AsyncCaptchaSolver: Send + Sync {
+    /// Recognize the code in `image`.
+    fn solve<'a>(&'a self, image: &'a [u8]) -> SolveFuture<'a>;
+
+    /// Recognize the code in `image`, along with how sure the solver is about it, see
+    /// [`CaptchaSolver::solve_with_confidence`].
+    ///
+    /// Defaults to [`solve`](Self::solve) with a confidence of `1.0`.
+    fn solve_with_confidence<'a>(&'a self, image: &'a [u8]) -> SolveFuture<'a, (String, f32)> {
+        Box::pin(async move { self.solve(image).await.map(|code| (code, 1.0)) })
+    }
+
+    /// Recognize the code in `image`, along with the characters read from it, see
+    /// [`CaptchaSolver::solve_with_text`].
+    ///
+    /// Defaults to [`solve_with_confidence`](Self::solve_with_confidence), reading the code
+    /// itself.
+    fn solve_with_text<'a>(&'a self, image: &'a [u8]) -> SolveFuture<'a, Solution> {
+        Box::pin(async move {
+            self.solve_with_confidence(image)
+                .await
+                .map(|(code, confidence)| Solution::new(code, confidence))
+        })
+    }
+}
+
+impl<T: CaptchaSolver + ?Sized> AsyncCaptchaSolver for T {
+    fn solve<'a>(&'a self, image: &'a [u8]) -> SolveFuture<'a> {
+        Box::pin(std::future::ready(CaptchaSolver::solve(self, image)))
+    }
+
+    fn solve_with_confidence<'a>(&'a self, image: &'a [u8]) -> SolveFuture<'a, (String, f32)> {
+        Box::pin(std::future::ready(CaptchaSolver::solve_with_confidence(
+            self, image,
+        )))
+    }
+
+    fn solve_with_text<'a>(&'a self, image: &'a [u8]) -> SolveFuture<'a, Solution> {
+        Box::pin(std::future::ready(CaptchaSolver::solve_with_text(
+            self, image,
+        )))
+    }
+}
+
+impl<F> CaptchaSolver for F
+where
+    F: Fn(&[u8]) -> Result<String, SolverError> + Send + Sync,
+{
+    fn solve(&self, image: &[u8]) -> Result<String, SolverError> {
+        self(image)
+    }
+}
+
+///
+/// The built-in solver, matching each digit against templates of the USTC validate code.
+/// See [`captcha`](crate::captcha) module for the recognizer itself.
+///
+/// This is the default solver of [`CasClient`](crate::CasClient). Using this type
+/// requires enabling `validate-code` or `validate-code-lite` feature.
+///
+#[cfg(feature = "validate-code-lite")]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TemplateSolver;
+
+#[cfg(feature = "validate-code-lite")]
+impl CaptchaSolver for TemplateSolver {
+    fn solve(&self, image: &[u8]) -> Result<String, SolverError> {
+        CaptchaSolver::solve_with_confidence(self, image).map(|(code, _)| code)
+    }
+
+    /// The confidence is the one of [`Recognition`](crate::captcha::Recognition).
+    fn solve_with_confidence(&self, image: &[u8]) -> Result<(String, f32), SolverError> {
+        CaptchaSolver::solve_with_confidence(&crate::captcha::Recognizer::default(), image)
+    }
+
+    fn solve_with_text(&self, image: &[u8]) -> Result<Solution, SolverError> {
+        CaptchaSolver::solve_with_text(&crate::captcha::Recognizer::default(), image)
+    }
+}
+
+///
+/// A solver classifying each digit with the convolutional network bundled with the crate,
+/// see [`CnnModel`](crate::captcha::CnnModel).
+///
+/// Its accuracy on real validate codes has not been compared with the one of
+/// [`TemplateSolver`]; `ustc-cas-captcha-eval --compare` measures both over a corpus, such
+/// as one collected with [`CorpusRecorder`](crate::CorpusRecorder). Using this type
+/// requires enabling `cnn` feature.
+///
+#[cfg(feature = "cnn")]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct CnnSolver;
+
+#[cfg(feature = "cnn")]
+impl CaptchaSolver for CnnSolver {
+    fn solve(&self, image: &[u8]) -> Result<String, SolverError> {
+        CaptchaSolver::solve_with_confidence(self, image).map(|(code, _)| code)
+    }
+
+    /// The confidence is the one of [`Recognition`](crate::captcha::Recognition).
+    fn solve_with_confidence(&self, image: &[u8]) -> Result<(String, f32), SolverError> {
+        CaptchaSolver::solve_with_confidence(&cnn_recognizer(), image)
+    }
+
+    fn solve_with_text(&self, image: &[u8]) -> Result<Solution, SolverError> {
+        CaptchaSolver::solve_with_text(&cnn_recognizer(), image)
+    }
+}
+
+#[cfg(feature = "cnn")]
+fn cnn_recognizer() -> crate::captcha::Recognizer {
+    crate::captcha::Recognizer::new().cnn(crate::captcha::CnnModel::bundled())
+}
+
+#[cfg(feature = "validate-code-lite")]
+impl CaptchaSolver for crate::captcha::Recognizer {
+    fn solve(&self, image: &[u8]) -> Result<String, SolverError> {
+        CaptchaSolver::solve_with_confidence(self, image).map(|(code, _)| code)
+    }
+
+    /// The confidence is the one of [`Recognition`](crate::captcha::Recognition).
+    fn solve_with_confidence(&self, image: &[u8]) -> Result<(String, f32), SolverError> {
+        CaptchaSolver::solve_with_text(self, image).map(|s| (s.code, s.confidence))
+    }
+
+    /// The text is the one of [`Recognition`](crate::captcha::Recognition).
+    fn solve_with_text(&self, image: &[u8]) -> Result<Solution, SolverError> {
+        let recognition = self
+            .recognize(image)
+            .map_err(|e| CasError::with_source(ErrorKind::ValidateCodeError, e))?;
+        Ok(Solution {
+            code: recognition.code(),
+            text: recognition.text(),
+            confidence: recognition.confidence(),
+        })
+    }
+}
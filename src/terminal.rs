@@ -0,0 +1,164 @@
+use crate::captcha::LumaImage;
+use crate::{CaptchaSolver, SolverError};
+use std::fmt::{Debug, Formatter};
+use std::io::{stderr, stdin, BufRead, BufReader, Write};
+use std::sync::Mutex;
+
+/// The way [`TerminalSolver`] draws the validate code.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ArtStyle {
+    /// Unicode half blocks (`▀`, `▄` and `█`), two pixels per character. The sharpest
+    /// style, needs a terminal font with block elements. The default.
+    HalfBlock,
+    /// Plain ASCII characters shaded by darkness, for terminals without unicode.
+    Ascii,
+}
+
+///
+/// A human-in-the-loop solver, drawing the validate code in the terminal and reading
+/// the answer typed by the user.
+///
+/// The image is drawn with text only, see [`ArtStyle`], so no GUI is needed. By default
+/// the picture and prompt go to stderr and the answer is read from stdin. Other streams,
+/// such as the ones of a TUI, can be used with [`TerminalSolver::with_io`].
+///
+/// Works with both async and blocking [`CasClient`](crate::CasClient). Note that
+/// waiting for input blocks the current thread.
+///
+/// Using this type requires enabling `validate-code` or `validate-code-lite` feature.
+///
+/// # Example
+/// ```rust
+/// use ustc_cas::{CasClient, TerminalSolver};
+///
+/// let client = CasClient::builder()
+///     .captcha_solver(TerminalSolver::new())
+///     .build();
+/// ```
+///
+pub struct TerminalSolver {
+    input: Mutex<Box<dyn BufRead + Send>>,
+    output: Mutex<Box<dyn Write + Send>>,
+    style: ArtStyle,
+    max_width: u32,
+}
+
+impl TerminalSolver {
+    /// Create a solver drawing to stderr and reading from stdin.
+    pub fn new() -> Self {
+        Self::with_io(BufReader::new(stdin()), stderr())
+    }
+
+    /// Create a solver drawing to `output` and reading the answer from `input`.
+    pub fn with_io<R, W>(input: R, output: W) -> Self
+    where
+        R: BufRead + Send + 'static,
+        W: Write + Send + 'static,
+    {
+        Self {
+            input: Mutex::new(Box::new(input)),
+            output: Mutex::new(Box::new(output)),
+            style: ArtStyle::default(),
+            max_width: 80,
+        }
+    }
+
+    /// Set the drawing style. Defaults to [`ArtStyle::HalfBlock`].
+    #[must_use]
+    pub fn style(mut self, style: ArtStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Shrink images wider than `columns` characters to fit. Defaults to 80.
+    #[must_use]
+    pub fn max_width(mut self, columns: u32) -> Self {
+        self.max_width = columns.max(1);
+        self
+    }
+
+    /// Draw `image` as text, one line per terminal row.
+    pub fn render(&self, image: &[u8]) -> Result<String, SolverError> {
+        let mut img = LumaImage::decode(image)?;
+        if img.width() > self.max_width {
+            // in u64, as the product may not fit in u32; the quotient is at most the height
+            let height = img.height() as u64 * self.max_width as u64 / img.width() as u64;
+            img = img.resize(self.max_width, height as u32);
+        }
+
+        let (width, height) = (img.width(), img.height());
+        let luma = |x, y| {
+            if y < height {
+                img.get(x, y)
+            } else {
+                255
+            }
+        };
+        let mut art = String::new();
+        for y in (0..height).step_by(2) {
+            let mut line = String::new();
+            for x in 0..width {
+                line.push(match self.style {
+                    ArtStyle::HalfBlock => match (luma(x, y) < 128, luma(x, y + 1) < 128) {
+                        (true, true) => '█',
+                        (true, false) => '▀',
+                        (false, true) => '▄',
+                        (false, false) => ' ',
+                    },
+                    ArtStyle::Ascii => {
+                        const RAMP: &[u8] = b" .:-=+*#%@";
+                        let light = (luma(x, y) as usize + luma(x, y + 1) as usize) / 2;
+                        RAMP[(255 - light) * (RAMP.len() - 1) / 255] as char
+                    }
+                });
+            }
+            art += line.trim_end();
+            art.push('\n');
+        }
+        Ok(art)
+    }
+}
+
+impl CaptchaSolver for TerminalSolver {
+    fn solve(&self, image: &[u8]) -> Result<String, SolverError> {
+        let art = self.render(image)?;
+        {
+            let mut output = self.output.lock().map_err(|_| "terminal output poisoned")?;
+            write!(output, "{art}validate code: ")?;
+            output.flush()?;
+        }
+
+        let mut answer = String::new();
+        self.input
+            .lock()
+            .map_err(|_| "terminal input poisoned")?
+            .read_line(&mut answer)?;
+        let answer = answer.trim();
+        if answer.is_empty() {
+            Err("no validate code entered".into())
+        } else {
+            Ok(answer.into())
+        }
+    }
+}
+
+impl Default for ArtStyle {
+    fn default() -> Self {
+        ArtStyle::HalfBlock
+    }
+}
+
+impl Default for TerminalSolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Debug for TerminalSolver {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TerminalSolver")
+            .field("style", &self.style)
+            .field("max_width", &self.max_width)
+            .finish_non_exhaustive()
+    }
+}
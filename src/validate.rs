@@ -0,0 +1,153 @@
+use super::*;
+use regex::Captures;
+
+///
+/// The CAS protocol version used to validate service tickets, see
+/// [`CasClientBuilder::cas_protocol`].
+///
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum CasProtocol {
+    /// CAS 2.0, validating at `/serviceValidate`. Attributes are only returned if the
+    /// server adds them to the 2.0 response on its own.
+    V2,
+    /// CAS 3.0, validating at `/p3/serviceValidate`, which returns the attributes of the
+    /// user.
+    V3,
+}
+
+impl Default for CasProtocol {
+    fn default() -> Self {
+        Self::V3
+    }
+}
+
+pub(crate) fn validate_url(base_url: &str, protocol: CasProtocol) -> String {
+    match protocol {
+        CasProtocol::V2 => format!("{base_url}/serviceValidate"),
+        CasProtocol::V3 => format!("{base_url}/p3/serviceValidate"),
+    }
+}
+
+/// Parse the XML response of `serviceValidate` into the principal, or the error it tells.
+///
+/// Namespace prefixes are ignored, so `<cas:user>` and `<user>` are the same.
+pub(crate) fn parse_validation(xml: &str) -> Result<Principal, CasError> {
+    static SUCCESS_RE: Lazy<Regex> = Lazy::new(|| element_re("authenticationSuccess"));
+    static FAILURE_RE: Lazy<Regex> = Lazy::new(|| element_re("authenticationFailure"));
+    static USER_RE: Lazy<Regex> = Lazy::new(|| element_re("user"));
+    static ATTRIBUTES_RE: Lazy<Regex> = Lazy::new(|| element_re("attributes"));
+    static CODE_RE: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r#"\bcode\s*=\s*["']([^"']*)["']"#).unwrap());
+    // an element holding text only, or an empty one
+    static ATTRIBUTE_RE: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(
+            r"<(?:[\w-]+:)?([\w.-]+)\s*(?:/>|>((?:[^<]|<!\[CDATA\[[\s\S]*?\]\]>)*)</(?:[\w-]+:)?([\w.-]+)\s*>)",
+        )
+        .unwrap()
+    });
+
+    if let Some(cap) = FAILURE_RE.captures(xml) {
+        let code = CODE_RE
+            .captures(&cap[1])
+            .map_or(String::new(), |c| c[1].to_string());
+        let message = match xml_text(content(&cap)) {
+            text if text.is_empty() => code.clone(),
+            text => text,
+        };
+        return Err(CasError::with_message(
+            validation_error_kind(&code),
+            message,
+        ));
+    }
+
+    let success = match SUCCESS_RE.captures(xml) {
+        Some(cap) => content(&cap).to_string(),
+        None if is_maintenance_page(xml) => {
+            return Err(CasError::new(ErrorKind::ServiceUnavailable))
+        }
+        None => return Err(CasError::new(ErrorKind::InvalidResponse)),
+    };
+    let user = USER_RE
+        .captures(&success)
+        .map(|cap| xml_text(content(&cap)))
+        .filter(|user| !user.is_empty())
+        .ok_or(CasError::new(ErrorKind::InvalidResponse))?;
+
+    let mut attributes: HashMap<String, Vec<String>> = HashMap::new();
+    if let Some(cap) = ATTRIBUTES_RE.captures(&success) {
+        for attribute in ATTRIBUTE_RE.captures_iter(content(&cap)) {
+            let name = &attribute[1];
+            // elements holding other elements are not attributes
+            if attribute.get(3).map_or(false, |end| end.as_str() != name) {
+                continue;
+            }
+            let value = attribute
+                .get(2)
+                .map_or(String::new(), |v| xml_text(v.as_str()));
+            attributes.entry(name.into()).or_default().push(value);
+        }
+    }
+    Ok(Principal::new(user, attributes))
+}
+
+/// Match element `name` with any namespace prefix, capturing its attributes and content.
+fn element_re(name: &str) -> Regex {
+    Regex::new(&format!(
+        r"<(?:[\w-]+:)?{name}\b([^>]*?)(?:/>|>([\s\S]*?)</(?:[\w-]+:)?{name}\s*>)"
+    ))
+    .unwrap()
+}
+
+/// The content matched by [`element_re`], empty for an empty element.
+fn content<'a>(cap: &Captures<'a>) -> &'a str {
+    cap.get(2).map_or("", |m| m.as_str())
+}
+
+fn validation_error_kind(code: &str) -> ErrorKind {
+    match code {
+        "INVALID_SERVICE" => ErrorKind::ServiceUrlIncorrect,
+        "INTERNAL_ERROR" => ErrorKind::ServiceUnavailable,
+        _ => ErrorKind::TicketInvalid,
+    }
+}
+
+/// The text of XML content, with entities decoded and CDATA sections kept as is, trimmed.
+fn xml_text(content: &str) -> String {
+    static CDATA_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"<!\[CDATA\[([\s\S]*?)\]\]>").unwrap());
+
+    let mut text = String::new();
+    let mut last = 0;
+    for cap in CDATA_RE.captures_iter(content) {
+        let whole = cap.get(0).unwrap();
+        text += &unescape_xml(&content[last..whole.start()]);
+        text += &cap[1];
+        last = whole.end();
+    }
+    text += &unescape_xml(&content[last..]);
+    text.trim().into()
+}
+
+fn unescape_xml(text: &str) -> String {
+    static ENTITY_RE: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"&(#x[0-9a-fA-F]+|#[0-9]+|[a-z]+);").unwrap());
+
+    ENTITY_RE
+        .replace_all(text, |cap: &Captures| {
+            let entity = &cap[1];
+            let c = match entity {
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "amp" => Some('&'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                _ => match entity.strip_prefix("#x") {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => entity.strip_prefix('#').and_then(|n| n.parse().ok()),
+                }
+                .and_then(char::from_u32),
+            };
+            c.map_or(cap[0].to_string(), String::from)
+        })
+        .into_owned()
+}
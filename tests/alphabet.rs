@@ -0,0 +1,120 @@
+#![cfg(feature = "validate-code")]
+
+mod common;
+
+use common::synth::{self, Rng, Style};
+use ustc_cas::captcha::{
+    Alphabet, CaptchaErrorKind, CodeKind, LumaImage, Recognizer, TemplateTrainer, Templates,
+};
+use ustc_cas::CaptchaSolver;
+
+/// Draw `code` with a character every 19 pixels, so that up to six fit.
+fn image(code: &str, rng: &mut Rng) -> Vec<u8> {
+    let style = Style {
+        xs: (0..code.len() as u32).map(|i| 10 + 19 * i).collect(),
+        ..Style::default()
+    };
+    synth::jpeg(code, &style, rng)
+}
+
+/// A random code of `len` characters of `chars`.
+fn code(chars: &str, len: usize, rng: &mut Rng) -> String {
+    let chars: Vec<char> = chars.chars().collect();
+    (0..len)
+        .map(|_| chars[rng.below(chars.len() as u32) as usize])
+        .collect()
+}
+
+fn train(codes: impl Iterator<Item = String>, rng: &mut Rng) -> Templates {
+    let mut trainer = TemplateTrainer::new();
+    for code in codes {
+        let luma = LumaImage::decode(&image(&code, rng)).unwrap();
+        trainer.add(&luma, &code).unwrap();
+    }
+    trainer.build().unwrap()
+}
+
+#[test]
+fn alphanumeric_codes_are_recognized() {
+    let mut rng = Rng::new(81);
+    let chars = "0123456789EHLPTU";
+    let codes: Vec<String> = (0..40).map(|_| code(chars, 4, &mut rng)).collect();
+    let templates = train(codes.into_iter(), &mut rng);
+    assert_eq!(templates.len(), 16);
+
+    let recognizer = Recognizer::new().templates(templates.clone());
+    for _ in 0..20 {
+        let code = code(chars, 4, &mut rng);
+        let recognition = recognizer.recognize(&image(&code, &mut rng)).unwrap();
+        assert_eq!(recognition.code(), code);
+        assert_eq!(recognition.text(), code);
+    }
+
+    // letters are never answered outside the alphabet
+    let digits = Recognizer::new()
+        .templates(templates)
+        .alphabet(Alphabet::digits());
+    let image = image("7H2L", &mut rng);
+    let code = digits.recognize(&image).unwrap().code();
+    assert!(code.chars().all(|c| c.is_ascii_digit()), "{code}");
+    assert_eq!(&code[0..1], "7");
+    assert_eq!(&code[2..3], "2");
+}
+
+#[test]
+fn alphabet_without_templates_is_an_error() {
+    let image = image("1234", &mut Rng::new(82));
+    let recognizer = Recognizer::new().alphabet(Alphabet::letters());
+    let err = recognizer.recognize(&image).unwrap_err();
+    assert_eq!(err.kind(), CaptchaErrorKind::Templates);
+
+    assert!(Alphabet::alphanumeric().contains('q'));
+    assert_eq!(Alphabet::new("aba").chars(), ['a', 'b']);
+    assert_eq!(Alphabet::default(), Alphabet::digits());
+}
+
+#[test]
+fn arithmetic_codes_are_evaluated() {
+    let mut rng = Rng::new(83);
+    let codes = (0..40).map(|i| {
+        let op = if i % 2 == 0 { '+' } else { '-' };
+        format!("{}{op}{}=?", rng.below(10), rng.below(10))
+    });
+    let codes: Vec<String> = codes.collect();
+    let recognizer = Recognizer::new()
+        .templates(train(codes.into_iter(), &mut rng))
+        .kind(CodeKind::Arithmetic);
+
+    for (text, answer) in [
+        ("3+5=?", "8"),
+        ("12-7=?", "5"),
+        ("3-8=?", "-5"),
+        ("40+25", "65"),
+        ("9-4+1=", "6"),
+    ] {
+        let image = image(text, &mut rng);
+        let recognition = recognizer.recognize(&image).unwrap();
+        assert_eq!(recognition.text(), text);
+        assert_eq!(recognition.code(), answer);
+        assert_eq!(CaptchaSolver::solve(&recognizer, &image).unwrap(), answer);
+    }
+
+    for text in ["3+=?", "+5=?", "3=5", "7-?"] {
+        let err = recognizer.recognize(&image(text, &mut rng)).unwrap_err();
+        assert_eq!(err.kind(), CaptchaErrorKind::Expression, "{text}: {err}");
+    }
+}
+
+#[test]
+fn symbols_are_only_segmented_for_arithmetic_codes() {
+    let image = image("3-5", &mut Rng::new(84));
+    let err = Recognizer::new().recognize(&image).unwrap_err();
+    assert_eq!(err.kind(), CaptchaErrorKind::Segmentation);
+
+    // the bundled templates have no operators, but the `-` is found
+    let recognition = Recognizer::new()
+        .kind(CodeKind::Arithmetic)
+        .recognize(&image)
+        .unwrap();
+    assert_eq!(recognition.digits().len(), 3);
+}
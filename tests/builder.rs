@@ -0,0 +1,88 @@
+mod common;
+
+use common::{MockCas, SERVICE};
+use std::time::Duration;
+use ustc_cas::{CasClient, ErrorKind, Proxy};
+
+/// A server accepting connections but never responding.
+fn silent_server() -> (std::net::TcpListener, String) {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    (listener, url)
+}
+
+#[tokio::test]
+async fn user_agent_is_sent() {
+    let mock = MockCas::start();
+    CasClient::builder()
+        .base_url(mock.base_url())
+        .user_agent("ustc-cas-test")
+        .build()
+        .unwrap()
+        .get_ticket("PB00000000", "password", SERVICE)
+        .await
+        .unwrap();
+    let requests = mock.requests();
+    assert!(requests
+        .iter()
+        .all(|r| r.headers["user-agent"] == "ustc-cas-test"));
+}
+
+#[tokio::test]
+async fn slow_server_times_out() {
+    let (_listener, url) = silent_server();
+    let err = CasClient::builder()
+        .base_url(url)
+        .timeout(Duration::from_millis(200))
+        .build()
+        .unwrap()
+        .get_ticket("PB00000000", "password", SERVICE)
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Timeout);
+}
+
+#[tokio::test]
+async fn requests_go_through_the_proxy() {
+    let mock = MockCas::start();
+    let ticket = CasClient::builder()
+        .base_url("http://passport.ustc.invalid")
+        .proxy(Proxy::http(mock.base_url()).unwrap())
+        .build()
+        .unwrap()
+        .get_ticket("PB00000000", "password", SERVICE)
+        .await;
+    // the mock sees the absolute url of the real server
+    let requests = mock.requests();
+    assert!(!requests.is_empty(), "{ticket:?}");
+    assert!(requests[0]
+        .path
+        .starts_with("http://passport.ustc.invalid/login"));
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn blocking_builder_takes_the_same_options() {
+    let mock = MockCas::start();
+    let client = ustc_cas::blocking::CasClient::builder()
+        .base_url(mock.base_url())
+        .user_agent("ustc-cas-test")
+        .timeout(Duration::from_secs(5))
+        .connect_timeout(Duration::from_secs(5))
+        .build()
+        .unwrap();
+    client
+        .get_ticket("PB00000000", "password", SERVICE)
+        .unwrap();
+    assert_eq!(mock.requests()[0].headers["user-agent"], "ustc-cas-test");
+
+    let (_listener, url) = silent_server();
+    let err = ustc_cas::blocking::CasClient::builder()
+        .base_url(url)
+        .timeout(Duration::from_millis(200))
+        .build()
+        .unwrap()
+        .get_ticket("PB00000000", "password", SERVICE)
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Timeout);
+}
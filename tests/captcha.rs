@@ -0,0 +1,110 @@
+#![cfg(feature = "validate-code")]
+
+mod common;
+
+use common::synth::{self, Rng, Style};
+use ustc_cas::captcha::{self, CaptchaErrorKind, LumaImage};
+
+#[test]
+fn recognize_every_digit() {
+    let mut rng = Rng::new(1);
+    for code in ["0123", "4567", "8901", "2345", "6789"] {
+        let image = synth::jpeg(code, &Style::default(), &mut rng);
+        let recognition = captcha::recognize(&image).unwrap();
+        assert_eq!(recognition.code(), code);
+        assert_eq!(recognition.digits().len(), 4);
+        for digit in recognition.digits() {
+            assert!(digit.score > 0.9, "{digit:?}");
+            assert!(digit.confidence > 0.0, "{digit:?}");
+        }
+    }
+}
+
+#[test]
+fn decoded_image_can_be_recognized() {
+    let mut rng = Rng::new(2);
+    let img = synth::draw("9876", &Style::default(), &mut rng);
+    let luma = LumaImage::from_raw(img.width(), img.height(), img.into_raw()).unwrap();
+    assert_eq!(captcha::recognize_luma(&luma).unwrap().code(), "9876");
+}
+
+#[test]
+fn broken_image_is_an_error() {
+    let err = captcha::recognize(b"not an image").unwrap_err();
+    assert_eq!(err.kind(), CaptchaErrorKind::Decode);
+
+    let tiny = LumaImage::from_raw(10, 10, vec![255; 100]).unwrap();
+    let err = captcha::recognize_luma(&tiny).unwrap_err();
+    assert_eq!(err.kind(), CaptchaErrorKind::InvalidSize);
+}
+
+#[test]
+fn codes_of_other_lengths_and_spacing() {
+    let mut rng = Rng::new(4);
+    for (code, xs, width) in [
+        ("123", vec![10, 30, 50], 80),
+        ("90817", vec![5, 24, 43, 62, 81], 110),
+        ("246802", vec![20, 40, 60, 80, 100, 120], 150),
+    ] {
+        let style = Style {
+            xs,
+            width,
+            ..Style::default()
+        };
+        let image = synth::jpeg(code, &style, &mut rng);
+        assert_eq!(captcha::recognize(&image).unwrap().code(), code);
+    }
+}
+
+#[test]
+fn touching_digits_are_split() {
+    let style = Style {
+        xs: vec![28, 43, 58, 73],
+        ..Style::default()
+    };
+    let image = synth::jpeg("5831", &style, &mut Rng::new(5));
+    assert_eq!(captcha::recognize(&image).unwrap().code(), "5831");
+}
+
+#[test]
+fn implausible_segmentation_is_an_error() {
+    let blank = LumaImage::from_raw(130, 30, vec![235; 130 * 30]).unwrap();
+    let err = captcha::recognize_luma(&blank).unwrap_err();
+    assert_eq!(err.kind(), CaptchaErrorKind::Segmentation);
+
+    let cut_off = Style {
+        width: 60,
+        ..Style::default()
+    };
+    let image = synth::jpeg("1234", &cut_off, &mut Rng::new(3));
+    let err = captcha::recognize(&image).unwrap_err();
+    assert_eq!(err.kind(), CaptchaErrorKind::Segmentation);
+
+    let too_many = Style {
+        xs: (0..9).map(|i| 5 + i * 18).collect(),
+        width: 175,
+        ..Style::default()
+    };
+    let image = synth::jpeg("123456789", &too_many, &mut Rng::new(6));
+    let err = captcha::recognize(&image).unwrap_err();
+    assert_eq!(err.kind(), CaptchaErrorKind::Segmentation);
+}
+
+#[test]
+fn jittered_synthetic_codes_are_recognized() {
+    let corpus = synth::jittered_corpus(200, 11, |_, _| {});
+    let (mut digits, mut codes) = (0, 0);
+    for (code, image) in &corpus {
+        let recognized = captcha::recognize(image).unwrap().code();
+        digits += code
+            .chars()
+            .zip(recognized.chars())
+            .filter(|(a, b)| a == b)
+            .count();
+        codes += (*code == recognized) as usize;
+    }
+    let digit_accuracy = digits as f64 / (corpus.len() * 4) as f64;
+    let code_accuracy = codes as f64 / corpus.len() as f64;
+    assert!(digit_accuracy >= 0.99, "digit accuracy {digit_accuracy}");
+    assert!(code_accuracy >= 0.97, "code accuracy {code_accuracy}");
+}
@@ -0,0 +1,90 @@
+mod common;
+
+use common::{MockCas, SERVICE};
+use ustc_cas::{CasClient, LoginStep};
+
+fn client(mock: &MockCas) -> CasClient {
+    CasClient::builder()
+        .base_url(mock.base_url())
+        .build()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn login_without_validate_code_finishes_at_once() {
+    let mock = MockCas::start();
+    let step = client(&mock)
+        .start_login("PB00000000", "password", SERVICE)
+        .await
+        .unwrap();
+    assert!(matches!(step, LoginStep::Ticket(t) if t == "ST-PB00000000"));
+}
+
+#[tokio::test]
+async fn challenge_is_answered_in_the_same_session() {
+    let mock = MockCas::start();
+    common::with_validate_code(&mock, b"fake image".to_vec());
+    let client = client(&mock);
+
+    let challenge = match client
+        .start_login("PB00000000", "password", SERVICE)
+        .await
+        .unwrap()
+    {
+        LoginStep::Captcha(challenge) => challenge,
+        LoginStep::Ticket(_) => panic!("validate code should be requested"),
+    };
+    assert_eq!(challenge.image(), b"fake image");
+    assert_eq!(challenge.username(), "PB00000000");
+    assert!(mock.logins().is_empty());
+
+    let ticket = client.submit_captcha(challenge, "5678").await.unwrap();
+    assert_eq!(ticket, "ST-PB00000000");
+
+    let requests = mock.requests();
+    let session = &requests[0].cookies;
+    assert!(session.is_empty());
+    let login = &mock.logins()[0];
+    assert_eq!(login.form["LT"], "5678");
+    assert!(login.cookies["JSESSIONID"].starts_with("session-"));
+    assert!(requests
+        .iter()
+        .skip(1)
+        .all(|r| r.cookies["JSESSIONID"] == login.cookies["JSESSIONID"]));
+}
+
+#[cfg(not(feature = "validate-code-lite"))]
+#[tokio::test]
+async fn missing_solver_is_reported() {
+    let mock = MockCas::start();
+    common::with_validate_code(&mock, b"fake image".to_vec());
+
+    let err = client(&mock)
+        .get_ticket("PB00000000", "password", SERVICE)
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), ustc_cas::ErrorKind::CaptchaRequired);
+    assert!(mock.logins().is_empty());
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn blocking_challenge() {
+    let mock = MockCas::start();
+    common::with_validate_code(&mock, b"fake image".to_vec());
+    let client = ustc_cas::blocking::CasClient::builder()
+        .base_url(mock.base_url())
+        .build()
+        .unwrap();
+
+    match client
+        .start_login("PB00000000", "password", SERVICE)
+        .unwrap()
+    {
+        LoginStep::Captcha(challenge) => {
+            let ticket = client.submit_captcha(challenge, "5678").unwrap();
+            assert_eq!(ticket, "ST-PB00000000");
+        }
+        LoginStep::Ticket(_) => panic!("validate code should be requested"),
+    }
+}
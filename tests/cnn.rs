@@ -0,0 +1,158 @@
+#![cfg(all(feature = "cnn", feature = "validate-code"))]
+
+mod common;
+
+use common::synth::{self, Rng, Style};
+use ustc_cas::captcha::{CaptchaErrorKind, CnnModel, CnnTrainer, LumaImage, Recognizer};
+use ustc_cas::{CaptchaSolver, CnnSolver};
+
+fn corpus(size: usize, seed: u64, distort: bool) -> Vec<(String, Vec<u8>)> {
+    synth::jittered_corpus(size, seed, |style, rng| {
+        if distort {
+            style.bold = rng.below(2) == 0;
+            style.noise = 45;
+            style.ink = 70;
+            style.background = 205;
+            style.quality = 60;
+        }
+    })
+}
+
+/// Fraction of digits recognized right.
+fn digit_accuracy(recognizer: &Recognizer, corpus: &[(String, Vec<u8>)]) -> f64 {
+    let right: usize = corpus
+        .iter()
+        .map(|(code, image)| match recognizer.recognize(image) {
+            Ok(r) => code
+                .chars()
+                .zip(r.code().chars())
+                .filter(|(a, b)| a == b)
+                .count(),
+            Err(_) => 0,
+        })
+        .sum();
+    right as f64 / (corpus.len() * 4) as f64
+}
+
+#[test]
+fn bundled_model_recognizes_every_digit() {
+    let model = CnnModel::bundled();
+    assert_eq!(model.labels(), "0123456789".chars().collect::<Vec<_>>());
+    let recognizer = Recognizer::new().cnn(model);
+    let mut rng = Rng::new(71);
+    for code in ["0123", "4567", "8901", "2345", "6789"] {
+        let image = synth::jpeg(code, &Style::default(), &mut rng);
+        let recognition = recognizer.recognize(&image).unwrap();
+        assert_eq!(recognition.code(), code);
+        for digit in recognition.digits() {
+            assert!(digit.score > 0.5, "{digit:?}");
+            assert!(digit.confidence > 0.0, "{digit:?}");
+        }
+    }
+
+    let image = synth::jpeg("5820", &Style::default(), &mut rng);
+    assert_eq!(CnnSolver.solve(&image).unwrap(), "5820");
+}
+
+/// The network against the templates, on the same corpora. The bundled model was trained
+/// on plain renders of the same glyphs, so the distorted corpus only shows how each copes
+/// with distortions it was not built from, not how they do on real validate codes.
+#[test]
+fn synthetic_distortions_compared_with_templates() {
+    let templates = Recognizer::new();
+    let cnn = Recognizer::new().cnn(CnnModel::bundled());
+
+    let plain = corpus(100, 72, false);
+    let (t, c) = (
+        digit_accuracy(&templates, &plain),
+        digit_accuracy(&cnn, &plain),
+    );
+    assert!(c >= 0.99, "templates {t}, cnn {c}");
+
+    let distorted = corpus(100, 73, true);
+    let (t, c) = (
+        digit_accuracy(&templates, &distorted),
+        digit_accuracy(&cnn, &distorted),
+    );
+    assert!(c >= 0.97 && c >= t, "templates {t}, cnn {c}");
+}
+
+#[test]
+fn model_file_round_trip() {
+    let model = CnnModel::bundled();
+    let data = model.to_bytes().unwrap();
+    assert_eq!(CnnModel::from_bytes(&data).unwrap(), model);
+
+    for broken in [&data[..4], &data[..data.len() - 1], b"UCTP\x01\x18\x10\x0a"] {
+        let err = CnnModel::from_bytes(broken).unwrap_err();
+        assert_eq!(err.kind(), CaptchaErrorKind::Model);
+    }
+    let mut nan = data.clone();
+    let last = nan.len() - 4;
+    nan[last..].copy_from_slice(&f32::NAN.to_le_bytes());
+    assert_eq!(
+        CnnModel::from_bytes(&nan).unwrap_err().kind(),
+        CaptchaErrorKind::Model
+    );
+}
+
+#[test]
+fn trained_model_recognizes_codes() {
+    let decode = |data: &[u8]| LumaImage::decode(data).unwrap();
+    let mut trainer = CnnTrainer::new().epochs(8);
+    for (code, image) in corpus(40, 74, false) {
+        trainer.add(&decode(&image), &code).unwrap();
+    }
+    assert_eq!(trainer.counts().values().sum::<usize>(), 160);
+    let model = trainer.build().unwrap();
+    assert_eq!(trainer.build().unwrap(), model, "training is deterministic");
+
+    let recognizer = Recognizer::new().cnn(model);
+    let accuracy = digit_accuracy(&recognizer, &corpus(30, 75, false));
+    assert!(accuracy >= 0.95, "{accuracy}");
+}
+
+#[test]
+fn training_needs_two_labels() {
+    let mut rng = Rng::new(76);
+    let image = synth::jpeg("1111", &Style::default(), &mut rng);
+    let mut trainer = CnnTrainer::new();
+    trainer
+        .add(&LumaImage::decode(&image).unwrap(), "1111")
+        .unwrap();
+    assert_eq!(trainer.build().unwrap_err().kind(), CaptchaErrorKind::Model);
+}
+
+#[cfg(all(feature = "train", feature = "eval"))]
+#[test]
+fn binaries_train_and_compare_models() {
+    let dir = std::env::temp_dir().join(format!("ustc-cas-cnn-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    for (i, (code, image)) in corpus(30, 77, false).into_iter().enumerate() {
+        std::fs::write(dir.join(format!("{code}_{i}.jpg")), image).unwrap();
+    }
+    let output = std::env::temp_dir().join(format!("ustc-cas-cnn-{}.bin", std::process::id()));
+
+    let result = std::process::Command::new(env!("CARGO_BIN_EXE_ustc-cas-captcha-train"))
+        .args(["--cnn", "--epochs", "2"])
+        .arg(&dir)
+        .arg(&output)
+        .output()
+        .unwrap();
+    assert!(result.status.success(), "{result:?}");
+    assert!(CnnModel::load(&output).is_ok());
+
+    let result = std::process::Command::new(env!("CARGO_BIN_EXE_ustc-cas-captcha-eval"))
+        .arg("--compare")
+        .arg(&dir)
+        .output()
+        .unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    std::fs::remove_file(&output).unwrap();
+    assert!(result.status.success(), "{result:?}");
+    let stdout = String::from_utf8_lossy(&result.stdout);
+    assert!(
+        stdout.contains("== templates") && stdout.contains("== cnn"),
+        "{stdout}"
+    );
+}
@@ -0,0 +1,353 @@
+//! a tiny in-process imitation of the passport server, good enough to drive the login flow.
+
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+pub const SERVICE: &str = "https://jw.ustc.edu.cn/ucas-sso/login";
+
+/// A request as seen by the mock server.
+#[derive(Clone, Debug)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub query: HashMap<String, String>,
+    pub cookies: HashMap<String, String>,
+    pub form: HashMap<String, String>,
+    /// headers by lowercase name.
+    pub headers: HashMap<String, String>,
+}
+
+/// A response produced by the mock server.
+#[derive(Clone, Debug, Default)]
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn ok<B: Into<Vec<u8>>>(body: B) -> Self {
+        Self {
+            status: 200,
+            headers: vec![],
+            body: body.into(),
+        }
+    }
+
+    pub fn redirect(location: &str) -> Self {
+        Self {
+            status: 302,
+            headers: vec![("Location".into(), location.into())],
+            body: vec![],
+        }
+    }
+
+    pub fn status(status: u16) -> Self {
+        Self {
+            status,
+            headers: vec![],
+            body: vec![],
+        }
+    }
+
+    pub fn cookie(mut self, name: &str, value: &str) -> Self {
+        self.headers
+            .push(("Set-Cookie".into(), format!("{name}={value}; Path=/")));
+        self
+    }
+}
+
+type Handler = dyn Fn(&Request) -> Option<Response> + Send + Sync;
+
+/// The mock passport server.
+///
+/// By default it behaves like CAS without captcha: every password is accepted, the
+/// ticket is `ST-<username>`, and a `TGC` cookie remembers the logged in user.
+/// Single requests can be overridden with [`MockCas::route`].
+#[derive(Clone)]
+pub struct MockCas {
+    base_url: String,
+    state: Arc<State>,
+}
+
+struct State {
+    routes: Mutex<Vec<Box<Handler>>>,
+    requests: Mutex<Vec<Request>>,
+    sessions: AtomicUsize,
+}
+
+impl MockCas {
+    pub fn start() -> Self {
+        let std_listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        std_listener.set_nonblocking(true).unwrap();
+        let base_url = format!("http://{}", std_listener.local_addr().unwrap());
+        let state = Arc::new(State {
+            routes: Mutex::new(vec![]),
+            requests: Mutex::new(vec![]),
+            sessions: AtomicUsize::new(0),
+        });
+
+        let server = state.clone();
+        std::thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap();
+            runtime.block_on(async move {
+                let listener = TcpListener::from_std(std_listener).unwrap();
+                loop {
+                    let (stream, _) = listener.accept().await.unwrap();
+                    let state = server.clone();
+                    tokio::spawn(async move {
+                        let _ = serve(stream, state).await;
+                    });
+                }
+            });
+        });
+
+        Self { base_url, state }
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Add a handler consulted before the default behaviour. The first handler returning
+    /// `Some` wins, later added handlers are consulted first.
+    pub fn route<F>(&self, handler: F)
+    where
+        F: Fn(&Request) -> Option<Response> + Send + Sync + 'static,
+    {
+        self.state
+            .routes
+            .lock()
+            .unwrap()
+            .insert(0, Box::new(handler));
+    }
+
+    /// All requests received so far.
+    pub fn requests(&self) -> Vec<Request> {
+        self.state.requests.lock().unwrap().clone()
+    }
+
+    /// The login form posts received so far.
+    pub fn logins(&self) -> Vec<Request> {
+        self.requests()
+            .into_iter()
+            .filter(|r| r.method == "POST" && r.path == "/login")
+            .collect()
+    }
+}
+
+/// The html of the login page, roughly the same as the real one.
+pub fn login_page(cas_lt: &str, show_code: bool) -> String {
+    let show_code = if show_code { "1" } else { "0" };
+    format!(
+        r##"<html><body>
+<form id="loginForm" method="post" action="/login">
+<input type="hidden" id="CAS_LT" name="CAS_LT" value="">
+<input type="hidden" id="showCode" name="showCode" value="{show_code}">
+<input type="hidden" id="model" name="model" value="uplogin.jsp">
+<input type="hidden" id="warn" name="warn" value="">
+</form>
+<script>$("#CAS_LT").val("{cas_lt}");</script>
+</body></html>"##
+    )
+}
+
+fn default_response(state: &State, request: &Request) -> Response {
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/login") => {
+            let service = request.query.get("service").cloned().unwrap_or_default();
+            if let Some(user) = request.cookies.get("TGC") {
+                return Response::redirect(&format!("{service}?ticket=ST-{user}"));
+            }
+            let id = state.sessions.fetch_add(1, Ordering::SeqCst);
+            Response::ok(login_page(&format!("LT-{id}"), false))
+                .cookie("JSESSIONID", &format!("session-{id}"))
+        }
+        ("POST", "/login") => {
+            let user = request.form.get("username").cloned().unwrap_or_default();
+            let service = request
+                .form
+                .get("service")
+                .cloned()
+                .unwrap_or(SERVICE.into());
+            Response::redirect(&format!("{service}?ticket=ST-{user}")).cookie("TGC", &user)
+        }
+        ("GET", "/serviceValidate") | ("GET", "/p3/serviceValidate") => {
+            let ticket = request.query.get("ticket").cloned().unwrap_or_default();
+            let service = request.query.get("service").cloned().unwrap_or_default();
+            let body = match ticket.strip_prefix("ST-") {
+                _ if service != SERVICE => validation_failure("INVALID_SERVICE", &service),
+                Some(user) => validation_success(user, request.path.starts_with("/p3")),
+                None => validation_failure("INVALID_TICKET", &ticket),
+            };
+            Response::ok(body)
+        }
+        _ => Response::status(404),
+    }
+}
+
+/// The XML of a successful ticket validation, with a few attributes if `attributes`.
+pub fn validation_success(user: &str, attributes: bool) -> String {
+    let attributes = if attributes {
+        format!(
+            "<cas:attributes>
+            <cas:gid>2{user}</cas:gid>
+            <cas:name>张三</cas:name>
+            <cas:email>{user}@mail.ustc.edu.cn</cas:email>
+            <cas:email>{user}@ustc.edu.cn</cas:email>
+        </cas:attributes>"
+        )
+    } else {
+        String::new()
+    };
+    format!(
+        "<cas:serviceResponse xmlns:cas='http://www.yale.edu/tp/cas'>
+    <cas:authenticationSuccess>
+        <cas:user>{user}</cas:user>
+        {attributes}
+    </cas:authenticationSuccess>
+</cas:serviceResponse>"
+    )
+}
+
+/// The XML of a failed ticket validation.
+pub fn validation_failure(code: &str, subject: &str) -> String {
+    format!(
+        "<cas:serviceResponse xmlns:cas='http://www.yale.edu/tp/cas'>
+    <cas:authenticationFailure code=\"{code}\">
+        {code}: {subject}
+    </cas:authenticationFailure>
+</cas:serviceResponse>"
+    )
+}
+
+async fn serve(stream: TcpStream, state: Arc<State>) -> std::io::Result<()> {
+    let mut stream = BufReader::new(stream);
+    loop {
+        let mut line = String::new();
+        if stream.read_line(&mut line).await? == 0 {
+            return Ok(());
+        }
+        let mut parts = line.split_whitespace();
+        let method = parts.next().unwrap_or_default().to_string();
+        let target = parts.next().unwrap_or_default().to_string();
+
+        let mut headers = HashMap::new();
+        loop {
+            let mut line = String::new();
+            stream.read_line(&mut line).await?;
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some((k, v)) = line.split_once(':') {
+                headers.insert(k.trim().to_ascii_lowercase(), v.trim().to_string());
+            }
+        }
+        let length = headers
+            .get("content-length")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0);
+        let mut body = vec![0; length];
+        stream.read_exact(&mut body).await?;
+
+        let (path, query) = target.split_once('?').unwrap_or((&target, ""));
+        let cookies = headers
+            .get("cookie")
+            .map(|c| {
+                c.split(';')
+                    .filter_map(|kv| kv.trim().split_once('='))
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let request = Request {
+            method,
+            path: path.into(),
+            query: parse_urlencoded(query),
+            cookies,
+            form: parse_urlencoded(&String::from_utf8_lossy(&body)),
+            headers,
+        };
+        state.requests.lock().unwrap().push(request.clone());
+
+        let response = state
+            .routes
+            .lock()
+            .unwrap()
+            .iter()
+            .find_map(|route| route(&request))
+            .unwrap_or_else(|| default_response(&state, &request));
+
+        let mut head = format!("HTTP/1.1 {} Mock\r\n", response.status);
+        for (k, v) in &response.headers {
+            head += &format!("{k}: {v}\r\n");
+        }
+        head += &format!("Content-Length: {}\r\n\r\n", response.body.len());
+        stream.get_mut().write_all(head.as_bytes()).await?;
+        stream.get_mut().write_all(&response.body).await?;
+    }
+}
+
+fn parse_urlencoded(data: &str) -> HashMap<String, String> {
+    data.split('&')
+        .filter_map(|kv| kv.split_once('='))
+        .map(|(k, v)| (decode(k), decode(v)))
+        .collect()
+}
+
+fn decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("");
+                out.push(u8::from_str_radix(hex, 16).unwrap_or(b'?'));
+                i += 2;
+            }
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// The html CAS responds with when the login is rejected, showing `message`.
+pub fn error_page(message: &str) -> String {
+    format!(
+        r#"<html><body>
+<div class="login-tips">请使用统一身份认证账号登录</div>
+<div id="errormsg" class="alert alert-danger"><span>{message}</span></div>
+</body></html>"#
+    )
+}
+
+/// Serve the login page with `showCode` set, and `image` as the validate code.
+pub fn with_validate_code(mock: &MockCas, image: Vec<u8>) {
+    let sessions = AtomicUsize::new(1000);
+    mock.route(move |r| match (r.method.as_str(), r.path.as_str()) {
+        ("GET", "/login") => {
+            let id = sessions.fetch_add(1, Ordering::SeqCst);
+            Some(
+                Response::ok(login_page(&format!("LT-{id}"), true))
+                    .cookie("JSESSIONID", &format!("session-{id}")),
+            )
+        }
+        ("GET", "/validatecode.jsp") => Some(Response::ok(image.clone())),
+        _ => None,
+    });
+}
+
+#[cfg(feature = "validate-code")]
+pub mod synth;
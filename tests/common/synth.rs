@@ -0,0 +1,160 @@
+//! synthetic validate code images, drawn with the glyphs of the bundled templates.
+
+use image::codecs::jpeg::JpegEncoder;
+use image::{GrayImage, Luma};
+use std::collections::HashMap;
+
+pub const WIDTH: u32 = 130;
+pub const HEIGHT: u32 = 30;
+
+/// The glyphs of digit 0 to 9, and of the symbols and letters of `symbols.txt`, `true`
+/// is ink.
+pub fn glyphs() -> HashMap<char, Vec<Vec<bool>>> {
+    let text = [
+        include_str!("../fixtures/digits.txt"),
+        include_str!("../fixtures/symbols.txt"),
+    ];
+    let mut glyphs = HashMap::new();
+    let mut label = ' ';
+    for line in text
+        .iter()
+        .flat_map(|t| t.lines())
+        .filter(|l| !l.starts_with("//"))
+    {
+        if line.len() == 1 {
+            label = line.chars().next().unwrap();
+        } else {
+            let glyph: &mut Vec<_> = glyphs.entry(label).or_default();
+            glyph.push(line.chars().map(|c| c == '#').collect());
+        }
+    }
+    glyphs
+}
+
+/// How a synthetic image is drawn.
+#[derive(Clone, Debug)]
+pub struct Style {
+    pub background: u8,
+    pub ink: u8,
+    /// maximum of the uniform noise added to every pixel.
+    pub noise: u8,
+    /// left edge of every digit.
+    pub xs: Vec<u32>,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub quality: u8,
+    /// draw every stroke one pixel wider.
+    pub bold: bool,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            background: 235,
+            ink: 40,
+            noise: 30,
+            xs: vec![28, 49, 70, 91],
+            y: 4,
+            width: WIDTH,
+            height: HEIGHT,
+            quality: 90,
+            bold: false,
+        }
+    }
+}
+
+/// A tiny deterministic random generator, so that corpora are reproducible.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(
+            seed.wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407),
+        )
+    }
+
+    pub fn next(&mut self) -> u32 {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (self.0 >> 33) as u32
+    }
+
+    pub fn below(&mut self, n: u32) -> u32 {
+        self.next() % n
+    }
+}
+
+/// Draw `code` and return the luma image.
+pub fn draw(code: &str, style: &Style, rng: &mut Rng) -> GrayImage {
+    let glyphs = glyphs();
+    let mut img = GrayImage::from_pixel(style.width, style.height, Luma([style.background]));
+    for (c, &x0) in code.chars().zip(&style.xs) {
+        let glyph = &glyphs[&c];
+        for (dy, row) in glyph.iter().enumerate() {
+            for (dx, &ink) in row.iter().enumerate() {
+                let (x, y) = (x0 + dx as u32, style.y + dy as u32);
+                for x in x..=x + style.bold as u32 {
+                    if ink && x < style.width && y < style.height {
+                        img.put_pixel(x, y, Luma([style.ink]));
+                    }
+                }
+            }
+        }
+    }
+    if style.noise > 0 {
+        for p in img.pixels_mut() {
+            let noise = rng.below(style.noise as u32 * 2 + 1) as i32 - style.noise as i32;
+            p.0[0] = (p.0[0] as i32 + noise).clamp(0, 255) as u8;
+        }
+    }
+    img
+}
+
+/// Draw `code` and encode it as JPEG.
+pub fn jpeg(code: &str, style: &Style, rng: &mut Rng) -> Vec<u8> {
+    encode(&draw(code, style, rng), style.quality)
+}
+
+pub fn encode(img: &GrayImage, quality: u8) -> Vec<u8> {
+    let mut data = vec![];
+    JpegEncoder::new_with_quality(&mut data, quality)
+        .encode_image(img)
+        .unwrap();
+    data
+}
+
+/// A random code of `len` digits.
+pub fn code(len: usize, rng: &mut Rng) -> String {
+    (0..len)
+        .map(|_| char::from(b'0' + rng.below(10) as u8))
+        .collect()
+}
+
+/// `size` random codes, each drawn up to 2 pixels off its usual place, as the real server
+/// sometimes does. `vary` may change the style of every code further.
+pub fn jittered_corpus<F>(size: usize, seed: u64, mut vary: F) -> Vec<(String, Vec<u8>)>
+where
+    F: FnMut(&mut Style, &mut Rng),
+{
+    let mut rng = Rng::new(seed);
+    (0..size)
+        .map(|_| {
+            let code = code(4, &mut rng);
+            let mut style = Style {
+                xs: [28, 49, 70, 91]
+                    .iter()
+                    .map(|x| x + rng.below(5) - 2)
+                    .collect(),
+                y: 2 + rng.below(5),
+                ..Style::default()
+            };
+            vary(&mut style, &mut rng);
+            let image = jpeg(&code, &style, &mut rng);
+            (code, image)
+        })
+        .collect()
+}
@@ -0,0 +1,167 @@
+mod common;
+
+use common::{MockCas, Response, SERVICE};
+use ustc_cas::{CasClient, ErrorKind};
+
+fn client(mock: &MockCas) -> CasClient {
+    CasClient::builder()
+        .base_url(mock.base_url())
+        .build()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn http_status_is_reported() {
+    let mock = MockCas::start();
+    mock.route(|_| Some(Response::status(500)));
+
+    let err = client(&mock)
+        .get_ticket("PB00000000", "password", SERVICE)
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::HttpStatusError);
+    assert!(err.is_retryable());
+    assert!(!err.kind().is_retryable());
+
+    for (status, retryable) in [(429, true), (502, true), (403, false), (404, false)] {
+        let mock = MockCas::start();
+        mock.route(move |_| Some(Response::status(status)));
+        let err = client(&mock)
+            .get_ticket("PB00000000", "password", SERVICE)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::HttpStatusError, "{status}");
+        assert_eq!(err.is_retryable(), retryable, "{status}");
+    }
+}
+
+#[tokio::test]
+async fn maintenance_is_reported() {
+    let mock = MockCas::start();
+    mock.route(|_| Some(Response::status(503)));
+    let err = client(&mock)
+        .get_ticket("PB00000000", "password", SERVICE)
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::ServiceUnavailable);
+
+    let mock = MockCas::start();
+    mock.route(|_| {
+        Some(Response::ok(
+            "<html><body>系统维护中，请稍后访问</body></html>",
+        ))
+    });
+    let err = client(&mock)
+        .get_ticket("PB00000000", "password", SERVICE)
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::ServiceUnavailable);
+}
+
+#[tokio::test]
+async fn unexpected_page_is_reported() {
+    let mock = MockCas::start();
+    mock.route(|_| Some(Response::ok("<html><body>hello</body></html>")));
+
+    let err = client(&mock)
+        .get_ticket("PB00000000", "password", SERVICE)
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InterfaceChanged);
+    assert!(!err.is_retryable());
+}
+
+#[tokio::test]
+async fn invalid_location_is_reported() {
+    let mock = MockCas::start();
+    mock.route(|r| {
+        (r.method == "POST").then(|| Response {
+            status: 302,
+            headers: vec![("Location".into(), "https://jw.ustc.edu.cn/\u{7968}".into())],
+            body: vec![],
+        })
+    });
+
+    let err = client(&mock)
+        .get_ticket("PB00000000", "password", SERVICE)
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidResponse);
+}
+
+#[cfg(feature = "validate-code-lite")]
+#[tokio::test]
+async fn broken_validate_code_image_is_reported() {
+    let mock = MockCas::start();
+    common::with_validate_code(&mock, b"not an image".to_vec());
+
+    let err = client(&mock)
+        .get_ticket("PB00000000", "password", SERVICE)
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::ValidateCodeError);
+    assert!(mock.logins().is_empty());
+}
+
+async fn rejected_with(message: &'static str) -> ustc_cas::CasError {
+    let mock = MockCas::start();
+    mock.route(move |r| (r.method == "POST").then(|| Response::ok(common::error_page(message))));
+    CasClient::builder()
+        .base_url(mock.base_url())
+        .max_attempts(1)
+        .build()
+        .unwrap()
+        .get_ticket("PB00000000", "password", SERVICE)
+        .await
+        .unwrap_err()
+}
+
+#[tokio::test]
+async fn cas_message_is_attached() {
+    let cases = [
+        ("用户名或密码错误", ErrorKind::UserInfoIncorrect),
+        ("验证码错误", ErrorKind::ValidateCodeIncorrect),
+        ("账号已被冻结，请联系管理员", ErrorKind::AccountLocked),
+        ("登录失败次数过多，请稍后再试", ErrorKind::AccountLocked),
+    ];
+    for (message, kind) in cases {
+        let err = rejected_with(message).await;
+        assert_eq!(err.kind(), kind);
+        assert_eq!(err.message(), Some(message));
+        assert!(err.to_string().ends_with(message));
+    }
+}
+
+#[tokio::test]
+async fn page_without_message_means_wrong_password() {
+    let mock = MockCas::start();
+    mock.route(|r| (r.method == "POST").then(|| Response::ok("<html></html>")));
+    let err = client(&mock)
+        .get_ticket("PB00000000", "password", SERVICE)
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::UserInfoIncorrect);
+    assert_eq!(err.message(), None);
+}
+
+#[test]
+fn invalid_configuration_is_not_a_tls_error() {
+    let err = CasClient::builder()
+        .user_agent("ustc-cas\n")
+        .build()
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::BuilderError);
+    assert!(!err.is_retryable());
+    assert!(!err.kind().is_transport_error());
+}
+
+#[tokio::test]
+async fn invalid_base_url_is_a_builder_error() {
+    let client = CasClient::builder().base_url("not a url").build().unwrap();
+    let err = client
+        .get_ticket("PB00000000", "password", SERVICE)
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::BuilderError);
+    assert!(!err.is_retryable());
+}
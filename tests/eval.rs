@@ -0,0 +1,118 @@
+#![cfg(feature = "validate-code")]
+
+use std::path::{Path, PathBuf};
+use ustc_cas::captcha::{self, Evaluation};
+use ustc_cas::CorpusRecorder;
+
+/// The accuracy the bundled templates must keep on real validate codes, see
+/// [`real_corpus_keeps_its_accuracy`].
+const MIN_CODE_ACCURACY: f64 = 0.95;
+const MIN_DIGIT_ACCURACY: f64 = 0.98;
+
+/// Images drawn by `common::synth` from the glyphs the bundled templates were built from,
+/// with jitter, noise and JPEG artifacts. They only check the pipeline runs end to end,
+/// they cannot tell whether recognition of real validate codes regressed.
+fn synthetic_corpus() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/synthetic")
+}
+
+/// Recognize every image of `dir`, named like `CorpusRecorder` does, e.g. `1234_anything.jpg`.
+fn evaluate(dir: &Path) -> Evaluation {
+    let mut evaluation = Evaluation::new();
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        let name = path.file_name().unwrap().to_str().unwrap();
+        let text = CorpusRecorder::text(name.split(['_', '.']).next().unwrap()).unwrap();
+        let image = std::fs::read(&path).unwrap();
+        let recognized = captcha::recognize(&image).ok().map(|r| r.text());
+        evaluation.add(&text, recognized.as_deref());
+    }
+    evaluation
+}
+
+#[test]
+fn synthetic_corpus_smoke_test() {
+    let evaluation = evaluate(&synthetic_corpus());
+    assert!(evaluation.samples() >= 40);
+    assert!(
+        evaluation.code_accuracy() >= 0.9,
+        "synthetic corpus misrecognized\n{evaluation}"
+    );
+}
+
+/// The accuracy regression gate, over real validate codes of passport.ustc.edu.cn, such as
+/// those collected by `CorpusRecorder`. No real images are checked in, so run it with the
+/// directory of a corpus in `USTC_CAS_REAL_CORPUS`, and `cargo test -- --ignored`.
+#[test]
+#[ignore = "needs real validate codes in USTC_CAS_REAL_CORPUS"]
+fn real_corpus_keeps_its_accuracy() {
+    let dir = std::env::var_os("USTC_CAS_REAL_CORPUS")
+        .expect("USTC_CAS_REAL_CORPUS must name a directory of real validate codes");
+    let evaluation = evaluate(Path::new(&dir));
+    assert!(evaluation.samples() > 0, "no images in {dir:?}");
+    assert!(
+        evaluation.code_accuracy() >= MIN_CODE_ACCURACY
+            && evaluation.digit_accuracy() >= MIN_DIGIT_ACCURACY,
+        "accuracy regressed\n{evaluation}"
+    );
+}
+
+#[test]
+fn evaluation_counts_digits_and_codes() {
+    let mut evaluation = Evaluation::new();
+    evaluation.add("1234", Some("1234"));
+    evaluation.add("1234", Some("1284"));
+    evaluation.add("5678", None);
+    evaluation.add("5678", Some("567"));
+
+    assert_eq!(evaluation.samples(), 4);
+    assert_eq!(evaluation.code_accuracy(), 0.25);
+    assert_eq!(evaluation.digit_accuracy(), 7.0 / 16.0);
+    assert_eq!(evaluation.confused('3', '8'), 1);
+    assert_eq!(evaluation.confused('3', '3'), 1);
+    assert_eq!(evaluation.confused('5', '?'), 2);
+    assert_eq!(evaluation.accuracy_of('3'), Some(0.5));
+    assert_eq!(evaluation.accuracy_of('9'), None);
+
+    let report = evaluation.to_string();
+    assert!(report.contains("code accuracy: 25.00%"), "{report}");
+    assert!(report.contains("digit accuracy: 43.75%"), "{report}");
+    assert!(report.lines().any(|l| l.trim_start().starts_with('3')
+        && l.split_whitespace().collect::<Vec<_>>()
+            == ["3", ".", ".", "1", ".", ".", ".", ".", "1", ".", "50.00%"]));
+}
+
+#[cfg(feature = "eval")]
+#[test]
+fn evaluation_binary_fails_below_threshold() {
+    let run = |args: &[&std::ffi::OsStr]| {
+        std::process::Command::new(env!("CARGO_BIN_EXE_ustc-cas-captcha-eval"))
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    let result = run(&[
+        "--min-accuracy".as_ref(),
+        "0.95".as_ref(),
+        synthetic_corpus().as_ref(),
+    ]);
+    assert!(result.status.success(), "{result:?}");
+    assert!(String::from_utf8_lossy(&result.stdout).contains("samples: "));
+
+    // templates mistaking every 7 for an x
+    let mut data = captcha::Templates::bundled().to_bytes().unwrap();
+    data[8 + 7 * (1 + 4 * 21)] = b'x';
+    let templates = std::env::temp_dir().join(format!("ustc-cas-eval-{}.bin", std::process::id()));
+    std::fs::write(&templates, data).unwrap();
+    let result = run(&[
+        "--templates".as_ref(),
+        templates.as_ref(),
+        "--min-accuracy".as_ref(),
+        "0.95".as_ref(),
+        synthetic_corpus().as_ref(),
+    ]);
+    std::fs::remove_file(&templates).unwrap();
+    assert_eq!(result.status.code(), Some(1), "{result:?}");
+    assert!(String::from_utf8_lossy(&result.stdout).contains("is below 95.00%"));
+}
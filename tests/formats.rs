@@ -0,0 +1,99 @@
+#![cfg(feature = "validate-code")]
+
+mod common;
+
+use common::synth::{self, Rng, Style};
+use image::imageops::{self, FilterType};
+use image::GrayImage;
+use ustc_cas::captcha::{self, CaptchaErrorKind, ImageFormat, LumaImage};
+
+#[cfg(any(feature = "png", feature = "gif"))]
+fn encode(img: image::DynamicImage, format: image::ImageOutputFormat) -> Vec<u8> {
+    let mut data = std::io::Cursor::new(vec![]);
+    img.write_to(&mut data, format).unwrap();
+    data.into_inner()
+}
+
+#[test]
+fn format_is_detected() {
+    let jpeg = synth::jpeg("1234", &Style::default(), &mut Rng::new(3));
+    assert_eq!(ImageFormat::detect(&jpeg), Some(ImageFormat::Jpeg));
+    assert_eq!(
+        ImageFormat::detect(b"\x89PNG\r\n\x1a\n"),
+        Some(ImageFormat::Png)
+    );
+    assert_eq!(ImageFormat::detect(b"GIF89a"), Some(ImageFormat::Gif));
+    assert_eq!(ImageFormat::detect(b"BM"), None);
+    assert!(ImageFormat::Jpeg.is_supported());
+
+    let err = LumaImage::decode(b"BM not supported").unwrap_err();
+    assert_eq!(err.kind(), CaptchaErrorKind::Decode);
+}
+
+#[cfg(feature = "png")]
+#[test]
+fn recognize_png() {
+    assert!(ImageFormat::Png.is_supported());
+    let img = synth::draw("2468", &Style::default(), &mut Rng::new(1));
+    let png = encode(img.into(), image::ImageOutputFormat::Png);
+    assert_eq!(captcha::recognize(&png).unwrap().code(), "2468");
+}
+
+#[cfg(feature = "gif")]
+#[test]
+fn recognize_gif() {
+    assert!(ImageFormat::Gif.is_supported());
+    let img = synth::draw("1357", &Style::default(), &mut Rng::new(2));
+    let gif = encode(
+        image::DynamicImage::from(img).into_rgba8().into(),
+        image::ImageOutputFormat::Gif,
+    );
+    assert_eq!(captcha::recognize(&gif).unwrap().code(), "1357");
+}
+
+#[cfg(not(feature = "png"))]
+#[test]
+fn png_needs_feature() {
+    let err = captcha::recognize(b"\x89PNG\r\n\x1a\n").unwrap_err();
+    assert_eq!(err.kind(), CaptchaErrorKind::UnsupportedFormat);
+    assert!(err.to_string().contains("`png`"), "{err}");
+}
+
+#[cfg(not(feature = "gif"))]
+#[test]
+fn gif_needs_feature() {
+    let err = captcha::recognize(b"GIF89a").unwrap_err();
+    assert_eq!(err.kind(), CaptchaErrorKind::UnsupportedFormat);
+    assert!(err.to_string().contains("`gif`"), "{err}");
+}
+
+#[test]
+fn scaled_images_are_recognized() {
+    let img = synth::draw("5079", &Style::default(), &mut Rng::new(4));
+    for (width, height) in [(260, 60), (195, 45), (104, 24)] {
+        let scaled = imageops::resize(&img, width, height, FilterType::Triangle);
+        let jpeg = synth::encode(&scaled, 95);
+        let recognition = captcha::recognize(&jpeg).unwrap();
+        assert_eq!(recognition.code(), "5079", "{width}x{height}");
+    }
+}
+
+#[test]
+fn size_is_checked() {
+    let low = LumaImage::from_raw(130, 12, vec![255; 130 * 12]).unwrap();
+    let err = captcha::recognize_luma(&low).unwrap_err();
+    assert_eq!(err.kind(), CaptchaErrorKind::InvalidSize);
+    assert!(err.to_string().contains("130x12"), "{err}");
+
+    let narrow = LumaImage::from_raw(20, 60, vec![255; 20 * 60]).unwrap();
+    let err = captcha::recognize_luma(&narrow).unwrap_err();
+    assert_eq!(err.kind(), CaptchaErrorKind::InvalidSize);
+
+    let huge = LumaImage::from_raw(4000, 30, vec![255; 4000 * 30]).unwrap();
+    let err = captcha::recognize_luma(&huge).unwrap_err();
+    assert_eq!(err.kind(), CaptchaErrorKind::InvalidSize);
+
+    let huge = synth::encode(&GrayImage::from_pixel(3000, 40, image::Luma([255])), 50);
+    let err = LumaImage::decode(&huge).unwrap_err();
+    assert_eq!(err.kind(), CaptchaErrorKind::InvalidSize);
+}
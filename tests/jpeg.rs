@@ -0,0 +1,166 @@
+#![cfg(feature = "validate-code-lite")]
+
+use std::path::{Path, PathBuf};
+use ustc_cas::captcha::{self, CaptchaErrorKind, LumaImage};
+
+/// labeled images of a fixture directory.
+fn fixtures(dir: &str) -> Vec<(String, Vec<u8>)> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(dir);
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .collect();
+    paths.sort();
+    paths
+        .into_iter()
+        .map(|path| {
+            let name = path.file_name().unwrap().to_str().unwrap();
+            let code = name.split('_').next().unwrap().to_string();
+            (code, std::fs::read(&path).unwrap())
+        })
+        .collect()
+}
+
+#[test]
+fn builtin_decoder_recognizes_the_synthetic_corpus() {
+    let recognizer = captcha::Recognizer::new();
+    let mut correct = 0;
+    let corpus = fixtures("synthetic");
+    for (code, image) in &corpus {
+        let luma = LumaImage::decode_jpeg(image).unwrap();
+        assert_eq!((luma.width(), luma.height()), (130, 30));
+        if recognizer.recognize_luma(&luma).map(|r| r.code()).ok() == Some(code.clone()) {
+            correct += 1;
+        }
+    }
+    assert!(
+        correct * 100 >= corpus.len() * 95,
+        "{correct} of {}",
+        corpus.len()
+    );
+}
+
+/// images of other layouts than the encoder of `image` crate writes: chroma subsampled
+/// 4:2:0 and 4:2:2, and restart intervals.
+#[test]
+fn builtin_decoder_handles_sampling_and_restarts() {
+    let images = fixtures("jpeg");
+    assert_eq!(images.len(), 3);
+    for (code, image) in images {
+        let luma = LumaImage::decode_jpeg(&image).unwrap();
+        assert_eq!((luma.width(), luma.height()), (130, 30));
+        assert_eq!(captcha::recognize_luma(&luma).unwrap().code(), code);
+    }
+}
+
+#[test]
+fn broken_jpeg_is_an_error() {
+    let (_, image) = &fixtures("synthetic")[0];
+    for data in [&image[..2], &image[..image.len() / 3], b"\xff\xd8\xff\xd9"] {
+        let err = LumaImage::decode_jpeg(data).unwrap_err();
+        assert_eq!(err.kind(), CaptchaErrorKind::Decode);
+    }
+
+    // a progressive frame header
+    let progressive = b"\xff\xd8\xff\xc2\x00\x0b\x08\x00\x1e\x00\x82\x01\x01\x11\x00\xff\xd9";
+    let err = LumaImage::decode_jpeg(progressive).unwrap_err();
+    assert_eq!(err.kind(), CaptchaErrorKind::Decode);
+    assert!(err.to_string().contains("baseline"), "{err}");
+
+    // a DC table whose first value is category 40, and an AC table of category 15
+    let (_, image) = fixtures("jpeg")
+        .into_iter()
+        .find(|(code, _)| code == "9164")
+        .unwrap();
+    for (class, value) in [(0x00, 40), (0x10, 0x0f)] {
+        let mut data = image.clone();
+        let dht = (0..data.len() - 1)
+            .find(|&i| data[i..i + 2] == [0xff, 0xc4] && data[i + 4] & 0xf0 == class)
+            .unwrap();
+        let count: usize = data[dht + 5..dht + 21].iter().map(|&c| c as usize).sum();
+        assert!(count > 0);
+        data[dht + 21] = value;
+        let err = LumaImage::decode_jpeg(&data).unwrap_err();
+        assert_eq!(err.kind(), CaptchaErrorKind::Decode);
+        assert!(err.to_string().contains("huffman"), "{err}");
+    }
+
+    let huge = b"\xff\xd8\xff\xc0\x00\x0b\x08\x00\x1e\x0f\xa0\x01\x01\x11\x00\xff\xd9";
+    let err = LumaImage::decode_jpeg(huge).unwrap_err();
+    assert_eq!(err.kind(), CaptchaErrorKind::InvalidSize);
+}
+
+#[cfg(feature = "validate-code")]
+mod common;
+
+#[cfg(feature = "validate-code")]
+mod same_as_image_crate {
+    use super::common::synth::{self, Rng, Style};
+    use super::fixtures;
+    use image::codecs::jpeg::JpegEncoder;
+    use image::RgbImage;
+    use ustc_cas::captcha::{self, LumaImage};
+
+    fn assert_close(a: &LumaImage, b: &LumaImage, tolerance: u8) {
+        assert_eq!((a.width(), a.height()), (b.width(), b.height()));
+        let max = a
+            .pixels()
+            .iter()
+            .zip(b.pixels())
+            .map(|(&a, &b)| a.abs_diff(b))
+            .max()
+            .unwrap();
+        assert!(max <= tolerance, "pixels differ by {max}");
+    }
+
+    #[test]
+    fn grayscale_pixels_and_codes() {
+        for (_, image) in fixtures("synthetic") {
+            let builtin = LumaImage::decode_jpeg(&image).unwrap();
+            let reference = LumaImage::decode(&image).unwrap();
+            assert_close(&builtin, &reference, 2);
+            assert_eq!(
+                captcha::recognize_luma(&builtin).map(|r| r.code()).ok(),
+                captcha::recognize_luma(&reference).map(|r| r.code()).ok(),
+            );
+        }
+    }
+
+    #[test]
+    fn subsampled_codes() {
+        for (code, image) in fixtures("jpeg") {
+            let builtin = LumaImage::decode_jpeg(&image).unwrap();
+            let reference = LumaImage::decode(&image).unwrap();
+            assert_eq!((builtin.width(), builtin.height()), (130, 30));
+            assert_eq!(captcha::recognize_luma(&reference).unwrap().code(), code);
+            assert_eq!(captcha::recognize_luma(&builtin).unwrap().code(), code);
+        }
+    }
+
+    #[test]
+    fn colored_codes() {
+        let mut rng = Rng::new(61);
+        for _ in 0..20 {
+            let code = synth::code(4, &mut rng);
+            let gray = synth::draw(&code, &Style::default(), &mut rng);
+            let rgb = RgbImage::from_fn(gray.width(), gray.height(), |x, y| {
+                let g = gray.get_pixel(x, y).0[0];
+                image::Rgb([g, (g as u16 * 9 / 10) as u8, g / 2 + 100])
+            });
+            let mut image = vec![];
+            JpegEncoder::new_with_quality(&mut image, 85)
+                .encode_image(&rgb)
+                .unwrap();
+
+            let builtin = LumaImage::decode_jpeg(&image).unwrap();
+            let reference = LumaImage::decode(&image).unwrap();
+            assert_eq!(
+                captcha::recognize_luma(&builtin).map(|r| r.code()).ok(),
+                captcha::recognize_luma(&reference).map(|r| r.code()).ok(),
+            );
+            assert_eq!(captcha::recognize_luma(&builtin).unwrap().code(), code);
+        }
+    }
+}
@@ -0,0 +1,117 @@
+#![cfg(feature = "validate-code")]
+
+mod common;
+
+use common::synth::{self, Rng, Style};
+use image::{GrayImage, Luma};
+use ustc_cas::captcha::{CaptchaErrorKind, LumaImage, Pipeline, Recognizer, Stage, Threshold};
+
+fn luma(img: GrayImage) -> LumaImage {
+    LumaImage::from_raw(img.width(), img.height(), img.into_raw()).unwrap()
+}
+
+fn fixed() -> Recognizer {
+    Recognizer::new().pipeline(Pipeline::new().binarize(Threshold::Fixed(128)))
+}
+
+#[test]
+fn otsu_copes_with_other_colors() {
+    let mut rng = Rng::new(21);
+    let dark = Style {
+        background: 110,
+        ink: 20,
+        noise: 15,
+        ..Style::default()
+    };
+    let faint = Style {
+        background: 240,
+        ink: 170,
+        noise: 15,
+        ..Style::default()
+    };
+    for style in [dark, faint] {
+        let image = synth::jpeg("3071", &style, &mut rng);
+        assert_eq!(Recognizer::new().recognize(&image).unwrap().code(), "3071");
+        assert!(!matches!(fixed().recognize(&image), Ok(r) if r.code() == "3071"));
+    }
+}
+
+#[test]
+fn local_mean_copes_with_shaded_background() {
+    let mut rng = Rng::new(22);
+    let mut img = synth::draw("6482", &Style::default(), &mut rng);
+    for (x, _, p) in img.enumerate_pixels_mut() {
+        p.0[0] = p.0[0].saturating_sub((x * 120 / 130) as u8);
+    }
+    let pipeline = Pipeline::new()
+        .binarize(Threshold::LocalMean {
+            radius: 10,
+            offset: 40,
+        })
+        .remove_speckles(2);
+    let recognizer = Recognizer::new().pipeline(pipeline);
+    assert_eq!(
+        recognizer.recognize_luma(&luma(img)).unwrap().code(),
+        "6482"
+    );
+}
+
+#[test]
+fn median_and_speckle_removal_clean_noise() {
+    let mut rng = Rng::new(23);
+    let mut img = synth::draw("5190", &Style::default(), &mut rng);
+    for p in img.pixels_mut() {
+        if rng.below(100) < 3 {
+            p.0[0] = 0;
+        }
+    }
+    let img = luma(img);
+
+    let median = Recognizer::new().pipeline(Pipeline::new().median(1).binarize(Threshold::Otsu));
+    assert_eq!(median.recognize_luma(&img).unwrap().code(), "5190");
+    assert_eq!(
+        Recognizer::new().recognize_luma(&img).unwrap().code(),
+        "5190"
+    );
+}
+
+#[test]
+fn interference_lines_are_removed() {
+    let mut rng = Rng::new(24);
+    let mut img = synth::draw("2857", &Style::default(), &mut rng);
+    for x in 0..img.width() {
+        img.put_pixel(x, 8 + x / 26, Luma([30]));
+        img.put_pixel(x, 22 - x / 13, Luma([30]));
+    }
+    let img = luma(img);
+
+    let err = Recognizer::new().recognize_luma(&img).unwrap_err();
+    assert_eq!(err.kind(), CaptchaErrorKind::Segmentation);
+
+    let pipeline = Pipeline::default().remove_lines(2);
+    let recognizer = Recognizer::new().pipeline(pipeline);
+    assert_eq!(recognizer.recognize_luma(&img).unwrap().code(), "2857");
+}
+
+#[test]
+fn stages_run_in_order() {
+    let pipeline = Pipeline::new()
+        .binarize(Threshold::Otsu)
+        .stage(Stage::RemoveSpeckles { min_neighbors: 1 });
+    assert_eq!(
+        pipeline.stages(),
+        [
+            Stage::Binarize(Threshold::Otsu),
+            Stage::RemoveSpeckles { min_neighbors: 1 }
+        ]
+    );
+
+    let mut pixels = vec![200; 25];
+    pixels[12] = 50;
+    pixels[0] = 60;
+    pixels[1] = 60;
+    let image = LumaImage::from_raw(5, 5, pixels).unwrap();
+    let out = pipeline.apply(&image);
+    assert_eq!(out.get(2, 2), 255);
+    assert_eq!((out.get(0, 0), out.get(1, 0), out.get(4, 4)), (0, 0, 255));
+}
@@ -0,0 +1,96 @@
+mod common;
+
+use common::{MockCas, Response, SERVICE};
+use std::collections::HashMap;
+use ustc_cas::{CasClient, Gender, Principal};
+
+/// A validation response roughly the same as the one of USTC CAS.
+const USTC_RESPONSE: &str = r#"<cas:serviceResponse xmlns:cas="http://www.yale.edu/tp/cas">
+    <cas:authenticationSuccess>
+        <cas:user>PB00000007</cas:user>
+        <cas:attributes>
+            <cas:gid>2000000007</cas:gid>
+            <cas:zjhm>PB00000007</cas:zjhm>
+            <cas:name>李四</cas:name>
+            <cas:xbm>2</cas:xbm>
+            <cas:deptCode>011</cas:deptCode>
+            <cas:deptName>物理学院</cas:deptName>
+            <cas:email>lisi@mail.ustc.edu.cn</cas:email>
+            <cas:ryzxztdm>10</cas:ryzxztdm>
+            <cas:isFromNewLogin>true</cas:isFromNewLogin>
+            <cas:authenticationDate>2023-03-01T08:00:00.000+08:00[Asia/Shanghai]</cas:authenticationDate>
+        </cas:attributes>
+    </cas:authenticationSuccess>
+</cas:serviceResponse>"#;
+
+async fn validate(body: &'static str) -> Principal {
+    let mock = MockCas::start();
+    mock.route(move |r| {
+        r.path
+            .ends_with("serviceValidate")
+            .then(|| Response::ok(body))
+    });
+    CasClient::builder()
+        .base_url(mock.base_url())
+        .build()
+        .unwrap()
+        .validate_ticket("ST-7", SERVICE)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn known_attributes_are_typed() {
+    let principal = validate(USTC_RESPONSE).await;
+    assert_eq!(principal.user(), "PB00000007");
+    assert_eq!(principal.gid(), Some("2000000007"));
+    assert_eq!(principal.student_number(), Some("PB00000007"));
+    assert_eq!(principal.name(), Some("李四"));
+    assert_eq!(principal.gender(), Some(Gender::Female));
+    assert_eq!(principal.department_code(), Some("011"));
+    assert_eq!(principal.department(), Some("物理学院"));
+    assert_eq!(principal.email(), Some("lisi@mail.ustc.edu.cn"));
+    assert_eq!(principal.emails(), ["lisi@mail.ustc.edu.cn"]);
+    assert_eq!(principal.is_from_new_login(), Some(true));
+
+    // the rest is in the attribute map
+    assert_eq!(principal.attribute("ryzxztdm"), Some("10"));
+    assert_eq!(principal.attributes().len(), 10);
+}
+
+#[test]
+fn missing_or_odd_attributes_are_none() {
+    let principal = Principal::new("PB00000008", HashMap::new());
+    assert_eq!(principal.user(), "PB00000008");
+    assert_eq!(principal.name(), None);
+    assert_eq!(principal.gender(), None);
+    assert!(principal.emails().is_empty());
+
+    let attributes = [
+        ("name", vec!["".to_string()]),
+        ("xm", vec!["王五".to_string()]),
+        ("xbm", vec!["9".to_string()]),
+        ("isFromNewLogin", vec!["yes".to_string()]),
+    ];
+    let attributes = attributes.into_iter().map(|(k, v)| (k.to_string(), v));
+    let principal = Principal::new("PB00000008", attributes.collect());
+    assert_eq!(principal.name(), Some("王五"));
+    assert_eq!(principal.gender(), None);
+    assert_eq!(principal.is_from_new_login(), None);
+}
+
+#[cfg(feature = "serde")]
+#[tokio::test]
+async fn principal_round_trips_through_serde() {
+    let principal = validate(USTC_RESPONSE).await;
+    let json = serde_json::to_string(&principal).unwrap();
+    assert_eq!(serde_json::from_str::<Principal>(&json).unwrap(), principal);
+
+    let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(value["user"], "PB00000007");
+    assert_eq!(value["attributes"]["name"][0], "李四");
+
+    let bare: Principal = serde_json::from_str(r#"{"user":"PB00000009"}"#).unwrap();
+    assert_eq!(bare, Principal::new("PB00000009", HashMap::new()));
+    assert_eq!(serde_json::to_string(&Gender::Male).unwrap(), r#""Male""#);
+}
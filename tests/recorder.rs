@@ -0,0 +1,240 @@
+mod common;
+
+use common::{MockCas, Response, SERVICE};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use ustc_cas::{CaptchaSolver, CasClient, CorpusRecorder, LoginStep, Solution, SolverError};
+
+/// a JPEG signature is enough, the image is never decoded here.
+const IMAGE: &[u8] = &[0xff, 0xd8, 0xff, 0xe0, 1, 2, 3];
+
+/// Ask for a validate code and accept only `1234`.
+fn mock() -> MockCas {
+    let mock = MockCas::start();
+    common::with_validate_code(&mock, IMAGE.to_vec());
+    mock.route(|r| {
+        (r.method == "POST" && r.form.get("LT").map(String::as_str) != Some("1234"))
+            .then(|| Response::ok(common::error_page("验证码错误")))
+    });
+    mock
+}
+
+/// A solver answering `0000` first and `1234` afterwards.
+fn solver() -> impl Fn(&[u8]) -> Result<String, SolverError> {
+    let calls = AtomicUsize::new(0);
+    move |_: &[u8]| match calls.fetch_add(1, Ordering::SeqCst) {
+        0 => Ok("0000".into()),
+        _ => Ok("1234".into()),
+    }
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("ustc-cas-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+fn files(dir: &Path) -> Vec<String> {
+    let mut names: Vec<_> = std::fs::read_dir(dir)
+        .map(|d| {
+            d.filter_map(|e| e.ok())
+                .filter(|e| e.path().is_file())
+                .map(|e| e.file_name().to_string_lossy().into_owned())
+                .collect()
+        })
+        .unwrap_or_default();
+    names.sort();
+    names
+}
+
+#[tokio::test]
+async fn corpus_keeps_accepted_and_failed_codes_apart() {
+    let mock = mock();
+    let dir = temp_dir("corpus");
+    let client = CasClient::builder()
+        .base_url(mock.base_url())
+        .captcha_solver(solver())
+        .captcha_recorder(CorpusRecorder::new(&dir))
+        .build()
+        .unwrap();
+
+    let ticket = client
+        .get_ticket("PB00000000", "password", SERVICE)
+        .await
+        .unwrap();
+    assert_eq!(ticket, "ST-PB00000000");
+
+    let accepted = files(&dir);
+    let failed = files(&dir.join("failed"));
+    assert_eq!(accepted.len(), 1);
+    assert!(accepted[0].starts_with("1234_") && accepted[0].ends_with(".jpg"));
+    assert_eq!(failed.len(), 1);
+    assert!(failed[0].starts_with("0000_"));
+    assert_eq!(std::fs::read(dir.join(&accepted[0])).unwrap(), IMAGE);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn unknown_outcome_is_not_recorded() {
+    let mock = mock();
+    mock.route(|r| (r.method == "POST").then(|| Response::ok(common::error_page("密码错误"))));
+    let records = Arc::new(Mutex::new(vec![]));
+    let recorded = records.clone();
+    let client = CasClient::builder()
+        .base_url(mock.base_url())
+        .captcha_solver(solver())
+        .captcha_recorder(move |_: &[u8], _: &str, code: &str, accepted: bool| {
+            recorded.lock().unwrap().push((code.to_string(), accepted))
+        })
+        .build()
+        .unwrap();
+
+    client
+        .get_ticket("PB00000000", "wrong", SERVICE)
+        .await
+        .unwrap_err();
+    assert!(records.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn answers_to_challenges_are_recorded() {
+    let mock = mock();
+    let records = Arc::new(Mutex::new(vec![]));
+    let recorded = records.clone();
+    let client = CasClient::builder()
+        .base_url(mock.base_url())
+        .captcha_recorder(
+            move |image: &[u8], text: &str, code: &str, accepted: bool| {
+                assert_eq!(image, IMAGE);
+                assert_eq!(text, code);
+                recorded.lock().unwrap().push((code.to_string(), accepted))
+            },
+        )
+        .build()
+        .unwrap();
+
+    for code in ["4321", "1234"] {
+        let challenge = match client
+            .start_login("PB00000000", "password", SERVICE)
+            .await
+            .unwrap()
+        {
+            LoginStep::Captcha(challenge) => challenge,
+            step => panic!("{step:?}"),
+        };
+        let _ = client.submit_captcha(challenge, code).await;
+    }
+    assert_eq!(
+        *records.lock().unwrap(),
+        [("4321".to_string(), false), ("1234".to_string(), true)]
+    );
+}
+
+/// Reads `12+34=?` and answers its value, with the answer `12-34=?` first.
+struct Arithmetic(AtomicUsize);
+
+impl CaptchaSolver for Arithmetic {
+    fn solve(&self, image: &[u8]) -> Result<String, SolverError> {
+        self.solve_with_text(image).map(|s| s.code)
+    }
+
+    fn solve_with_text(&self, _image: &[u8]) -> Result<Solution, SolverError> {
+        let (text, code) = match self.0.fetch_add(1, Ordering::SeqCst) {
+            0 => ("12-34=?", "0000"),
+            _ => ("12+34=?", "1234"),
+        };
+        Ok(Solution {
+            code: code.into(),
+            text: text.into(),
+            confidence: 1.0,
+        })
+    }
+}
+
+#[tokio::test]
+async fn text_read_by_the_solver_is_recorded() {
+    let mock = mock();
+    let records = Arc::new(Mutex::new(vec![]));
+    let recorded = records.clone();
+    let client = CasClient::builder()
+        .base_url(mock.base_url())
+        .captcha_solver(Arithmetic(AtomicUsize::new(0)))
+        .captcha_recorder(move |_: &[u8], text: &str, code: &str, accepted: bool| {
+            recorded
+                .lock()
+                .unwrap()
+                .push((text.to_string(), code.to_string(), accepted))
+        })
+        .build()
+        .unwrap();
+
+    client
+        .get_ticket("PB00000000", "password", SERVICE)
+        .await
+        .unwrap();
+    let records = records.lock().unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0], ("12-34=?".into(), "0000".into(), false));
+    assert_eq!(records[1], ("12+34=?".into(), "1234".into(), true));
+}
+
+#[tokio::test]
+async fn corpus_is_labeled_with_the_text() {
+    let mock = mock();
+    let dir = temp_dir("arithmetic-corpus");
+    let client = CasClient::builder()
+        .base_url(mock.base_url())
+        .captcha_solver(Arithmetic(AtomicUsize::new(0)))
+        .captcha_recorder(CorpusRecorder::new(&dir))
+        .build()
+        .unwrap();
+
+    client
+        .get_ticket("PB00000000", "password", SERVICE)
+        .await
+        .unwrap();
+    let label = |name: &str| CorpusRecorder::text(name.split('_').next().unwrap()).unwrap();
+    assert_eq!(label(&files(&dir)[0]), "12+34=?");
+    assert_eq!(label(&files(&dir.join("failed"))[0]), "12-34=?");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn labels_can_be_reversed() {
+    for text in ["1234", "3+5=?", "3-5=?", "3%2B5", "a_b", "x/y*z", "中"] {
+        let label = CorpusRecorder::label(text);
+        assert!(
+            label
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'%'),
+            "{label}"
+        );
+        assert_eq!(CorpusRecorder::text(&label).as_deref(), Some(text));
+    }
+    assert_eq!(CorpusRecorder::label("3+5=?"), "3%2B5%3D%3F");
+    assert_ne!(CorpusRecorder::label("3+5"), CorpusRecorder::label("3-5"));
+    for label in ["3-5", "3%2", "3%zz", "%FF"] {
+        assert_eq!(CorpusRecorder::text(label), None, "{label}");
+    }
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn blocking_client_records_too() {
+    let mock = mock();
+    let dir = temp_dir("blocking-corpus");
+    let client = ustc_cas::blocking::CasClient::builder()
+        .base_url(mock.base_url())
+        .captcha_solver(solver())
+        .captcha_recorder(CorpusRecorder::new(&dir))
+        .build()
+        .unwrap();
+
+    client
+        .get_ticket("PB00000000", "password", SERVICE)
+        .unwrap();
+    assert_eq!(files(&dir).len(), 1);
+    assert_eq!(files(&dir.join("failed")).len(), 1);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,233 @@
+mod common;
+
+use common::{MockCas, Response, SERVICE};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use ustc_cas::{CaptchaSolver, CasClient, ErrorKind, SolverError};
+
+/// Serve a new image `img-<n>` on every fetch, and accept only the code `<n>` of the
+/// latest image of the session, like CAS does.
+fn mock() -> MockCas {
+    let mock = MockCas::start();
+    common::with_validate_code(&mock, vec![]);
+    let latest = Arc::new(Mutex::new(HashMap::new()));
+    let served = AtomicUsize::new(0);
+    let session = |r: &common::Request| r.cookies.get("JSESSIONID").cloned().unwrap_or_default();
+
+    let images = latest.clone();
+    mock.route(move |r| {
+        (r.path == "/validatecode.jsp").then(|| {
+            let n = served.fetch_add(1, Ordering::SeqCst);
+            images.lock().unwrap().insert(session(r), n);
+            Response::ok(format!("img-{n}"))
+        })
+    });
+    mock.route(move |r| {
+        let expected = latest
+            .lock()
+            .unwrap()
+            .get(&session(r))
+            .map(|n| n.to_string());
+        (r.method == "POST" && r.form.get("LT") != expected.as_ref())
+            .then(|| Response::ok(common::error_page("验证码错误")))
+    });
+    mock
+}
+
+/// Reads the number in the image, with the confidence given for that image.
+struct Unsure(Vec<f32>);
+
+impl CaptchaSolver for Unsure {
+    fn solve(&self, image: &[u8]) -> Result<String, SolverError> {
+        self.solve_with_confidence(image).map(|(code, _)| code)
+    }
+
+    fn solve_with_confidence(&self, image: &[u8]) -> Result<(String, f32), SolverError> {
+        let n: usize = String::from_utf8_lossy(&image[4..]).parse()?;
+        Ok((n.to_string(), self.0.get(n).copied().unwrap_or(1.0)))
+    }
+}
+
+fn fetches(mock: &MockCas) -> Vec<String> {
+    mock.requests()
+        .into_iter()
+        .filter(|r| r.path == "/validatecode.jsp")
+        .map(|r| r.cookies["JSESSIONID"].clone())
+        .collect()
+}
+
+#[tokio::test]
+async fn unsure_code_is_refetched_in_same_session() {
+    let mock = mock();
+    let client = CasClient::builder()
+        .base_url(mock.base_url())
+        .captcha_solver(Unsure(vec![0.1, 0.2, 0.9]))
+        .min_captcha_confidence(0.5)
+        .build()
+        .unwrap();
+
+    let ticket = client
+        .get_ticket("PB00000000", "password", SERVICE)
+        .await
+        .unwrap();
+    assert_eq!(ticket, "ST-PB00000000");
+
+    let fetches = fetches(&mock);
+    assert_eq!(fetches.len(), 3);
+    assert!(fetches.iter().all(|s| *s == fetches[0]));
+    let logins = mock.logins();
+    assert_eq!(logins.len(), 1);
+    assert_eq!(logins[0].form["LT"], "2");
+}
+
+#[tokio::test]
+async fn latest_code_is_submitted_when_refreshes_run_out() {
+    let mock = mock();
+    let client = CasClient::builder()
+        .base_url(mock.base_url())
+        .captcha_solver(Unsure(vec![0.1; 10]))
+        .min_captcha_confidence(0.5)
+        .max_captcha_refreshes(2)
+        .build()
+        .unwrap();
+
+    client
+        .get_ticket("PB00000000", "password", SERVICE)
+        .await
+        .unwrap();
+    assert_eq!(fetches(&mock).len(), 3);
+    assert_eq!(mock.logins()[0].form["LT"], "2");
+}
+
+#[tokio::test]
+async fn no_refetch_by_default() {
+    let mock = mock();
+    let client = CasClient::builder()
+        .base_url(mock.base_url())
+        .captcha_solver(Unsure(vec![0.0; 10]))
+        .build()
+        .unwrap();
+
+    client
+        .get_ticket("PB00000000", "password", SERVICE)
+        .await
+        .unwrap();
+    assert_eq!(fetches(&mock).len(), 1);
+}
+
+#[cfg(feature = "validate-code")]
+#[test]
+fn template_solver_reports_recognition_confidence() {
+    use common::synth::{self, Rng, Style};
+
+    let image = synth::jpeg("3946", &Style::default(), &mut Rng::new(51));
+    let (code, confidence) = ustc_cas::TemplateSolver
+        .solve_with_confidence(&image)
+        .unwrap();
+    let recognition = ustc_cas::captcha::recognize(&image).unwrap();
+    assert_eq!(code, "3946");
+    assert_eq!(confidence, recognition.confidence());
+    assert!(confidence > 0.0);
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn blocking_client_refetches_too() {
+    let mock = mock();
+    let client = ustc_cas::blocking::CasClient::builder()
+        .base_url(mock.base_url())
+        .captcha_solver(Unsure(vec![0.1, 0.9]))
+        .min_captcha_confidence(0.5)
+        .build()
+        .unwrap();
+
+    client
+        .get_ticket("PB00000000", "password", SERVICE)
+        .unwrap();
+    assert_eq!(fetches(&mock).len(), 2);
+    assert_eq!(mock.logins()[0].form["LT"], "1");
+}
+
+/// Fails to read the first `.0` images, then reads the number in the image.
+struct Failing(usize);
+
+impl CaptchaSolver for Failing {
+    fn solve(&self, image: &[u8]) -> Result<String, SolverError> {
+        let n: usize = String::from_utf8_lossy(&image[4..]).parse()?;
+        if n < self.0 {
+            return Err("unreadable".into());
+        }
+        Ok(n.to_string())
+    }
+}
+
+#[tokio::test]
+async fn unreadable_code_is_refetched() {
+    let mock = mock();
+    let client = CasClient::builder()
+        .base_url(mock.base_url())
+        .captcha_solver(Failing(2))
+        .build()
+        .unwrap();
+
+    client
+        .get_ticket("PB00000000", "password", SERVICE)
+        .await
+        .unwrap();
+    assert_eq!(fetches(&mock).len(), 3);
+    let logins = mock.logins();
+    assert_eq!(logins.len(), 1);
+    assert_eq!(logins[0].form["LT"], "2");
+}
+
+#[tokio::test]
+async fn solver_error_is_reported_when_refreshes_run_out() {
+    let mock = mock();
+    let client = CasClient::builder()
+        .base_url(mock.base_url())
+        .captcha_solver(Failing(usize::MAX))
+        .max_captcha_refreshes(2)
+        .build()
+        .unwrap();
+
+    let err = client
+        .get_ticket("PB00000000", "password", SERVICE)
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::ValidateCodeError);
+    assert_eq!(err.get_ref().unwrap().to_string(), "unreadable");
+    assert_eq!(fetches(&mock).len(), 3);
+    assert!(mock.logins().is_empty());
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn blocking_client_refetches_unreadable_code() {
+    let mock = mock();
+    let client = ustc_cas::blocking::CasClient::builder()
+        .base_url(mock.base_url())
+        .captcha_solver(Failing(1))
+        .max_captcha_refreshes(1)
+        .build()
+        .unwrap();
+
+    client
+        .get_ticket("PB00000000", "password", SERVICE)
+        .unwrap();
+    assert_eq!(fetches(&mock).len(), 2);
+    assert_eq!(mock.logins()[0].form["LT"], "1");
+
+    let mock = self::mock();
+    let client = ustc_cas::blocking::CasClient::builder()
+        .base_url(mock.base_url())
+        .captcha_solver(Failing(2))
+        .max_captcha_refreshes(1)
+        .build()
+        .unwrap();
+    let err = client
+        .get_ticket("PB00000000", "password", SERVICE)
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::ValidateCodeError);
+    assert_eq!(fetches(&mock).len(), 2);
+}
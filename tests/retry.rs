@@ -0,0 +1,74 @@
+mod common;
+
+use common::{MockCas, Response, SERVICE};
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use ustc_cas::{CasClient, ErrorKind};
+
+fn reject_code(mock: &MockCas, times: usize) {
+    let rejected = AtomicUsize::new(0);
+    mock.route(move |r| {
+        (r.method == "POST" && rejected.fetch_add(1, Ordering::SeqCst) < times)
+            .then(|| Response::ok(common::error_page("验证码错误")))
+    });
+}
+
+#[tokio::test]
+async fn rejected_code_is_retried_with_fresh_page() {
+    let mock = MockCas::start();
+    reject_code(&mock, 2);
+    let client = CasClient::builder()
+        .base_url(mock.base_url())
+        .max_attempts(3)
+        .build()
+        .unwrap();
+
+    let ticket = client
+        .get_ticket("PB00000000", "password", SERVICE)
+        .await
+        .unwrap();
+    assert_eq!(ticket, "ST-PB00000000");
+
+    let logins = mock.logins();
+    assert_eq!(logins.len(), 3);
+    let lts: HashSet<_> = logins.iter().map(|l| &l.form["CAS_LT"]).collect();
+    assert_eq!(lts.len(), 3);
+}
+
+#[tokio::test]
+async fn attempts_are_reported() {
+    let mock = MockCas::start();
+    reject_code(&mock, usize::MAX);
+    let client = CasClient::builder()
+        .base_url(mock.base_url())
+        .max_attempts(4)
+        .build()
+        .unwrap();
+
+    let err = client
+        .get_ticket("PB00000000", "password", SERVICE)
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::ValidateCodeIncorrect);
+    assert_eq!(err.attempts(), Some(4));
+    assert_eq!(err.message(), Some("验证码错误"));
+    assert_eq!(mock.logins().len(), 4);
+}
+
+#[tokio::test]
+async fn wrong_password_is_not_retried() {
+    let mock = MockCas::start();
+    mock.route(|r| (r.method == "POST").then(|| Response::ok(common::error_page("密码错误"))));
+    let client = CasClient::builder()
+        .base_url(mock.base_url())
+        .build()
+        .unwrap();
+
+    let err = client
+        .get_ticket("PB00000000", "password", SERVICE)
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::UserInfoIncorrect);
+    assert_eq!(err.attempts(), Some(1));
+    assert_eq!(mock.logins().len(), 1);
+}
@@ -0,0 +1,88 @@
+mod common;
+
+use common::{MockCas, SERVICE};
+use std::collections::HashSet;
+use ustc_cas::CasClient;
+
+fn assert_isolated(mock: &MockCas) {
+    let logins = mock.logins();
+    for login in &logins {
+        assert!(
+            !login.cookies.contains_key("TGC"),
+            "login of {} carried another session: {:?}",
+            login.form["username"],
+            login.cookies
+        );
+    }
+    let sessions: HashSet<_> = logins.iter().map(|l| &l.cookies["JSESSIONID"]).collect();
+    assert_eq!(sessions.len(), logins.len());
+}
+
+#[tokio::test]
+async fn sequential_logins_do_not_share_cookies() {
+    let mock = MockCas::start();
+    let client = CasClient::builder()
+        .base_url(mock.base_url())
+        .build()
+        .unwrap();
+
+    for user in ["PB00000001", "PB00000002", "PB00000001"] {
+        let ticket = client.get_ticket(user, "password", SERVICE).await.unwrap();
+        assert_eq!(ticket, format!("ST-{user}"));
+    }
+    assert_isolated(&mock);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn interleaved_logins_do_not_share_cookies() {
+    let mock = MockCas::start();
+    let client = CasClient::builder()
+        .base_url(mock.base_url())
+        .build()
+        .unwrap();
+
+    let tasks: Vec<_> = (0..16)
+        .map(|i| {
+            let client = client.clone();
+            tokio::spawn(async move {
+                let user = format!("PB{i:08}");
+                let ticket = client.get_ticket(&user, "password", SERVICE).await.unwrap();
+                (user, ticket)
+            })
+        })
+        .collect();
+
+    for task in tasks {
+        let (user, ticket) = task.await.unwrap();
+        assert_eq!(ticket, format!("ST-{user}"));
+    }
+    assert_eq!(mock.logins().len(), 16);
+    assert_isolated(&mock);
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn blocking_logins_do_not_share_cookies() {
+    let mock = MockCas::start();
+    let client = ustc_cas::blocking::CasClient::builder()
+        .base_url(mock.base_url())
+        .build()
+        .unwrap();
+
+    let threads: Vec<_> = (0..8)
+        .map(|i| {
+            let client = client.clone();
+            std::thread::spawn(move || {
+                let user = format!("PB{i:08}");
+                let ticket = client.get_ticket(&user, "password", SERVICE).unwrap();
+                (user, ticket)
+            })
+        })
+        .collect();
+
+    for thread in threads {
+        let (user, ticket) = thread.join().unwrap();
+        assert_eq!(ticket, format!("ST-{user}"));
+    }
+    assert_isolated(&mock);
+}
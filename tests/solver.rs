@@ -0,0 +1,57 @@
+mod common;
+
+use common::{MockCas, SERVICE};
+use std::sync::{Arc, Mutex};
+use ustc_cas::{AsyncCaptchaSolver, CasClient, ErrorKind, SolveFuture, SolverError};
+
+#[tokio::test]
+async fn custom_solver_receives_image() {
+    let mock = MockCas::start();
+    common::with_validate_code(&mock, b"fake image".to_vec());
+
+    let seen = Arc::new(Mutex::new(vec![]));
+    let recorder = seen.clone();
+    let client = CasClient::builder()
+        .base_url(mock.base_url())
+        .captcha_solver(move |image: &[u8]| -> Result<String, SolverError> {
+            recorder.lock().unwrap().push(image.to_vec());
+            Ok("1234".into())
+        })
+        .build()
+        .unwrap();
+
+    let ticket = client
+        .get_ticket("PB00000000", "password", SERVICE)
+        .await
+        .unwrap();
+    assert_eq!(ticket, "ST-PB00000000");
+    assert_eq!(*seen.lock().unwrap(), vec![b"fake image".to_vec()]);
+    assert_eq!(mock.logins()[0].form["LT"], "1234");
+}
+
+struct Failing;
+
+impl AsyncCaptchaSolver for Failing {
+    fn solve<'a>(&'a self, _image: &'a [u8]) -> SolveFuture<'a> {
+        Box::pin(async { Err("solver offline".into()) })
+    }
+}
+
+#[tokio::test]
+async fn solver_error_is_reported() {
+    let mock = MockCas::start();
+    common::with_validate_code(&mock, b"fake image".to_vec());
+    let client = CasClient::builder()
+        .base_url(mock.base_url())
+        .captcha_solver(Failing)
+        .build()
+        .unwrap();
+
+    let err = client
+        .get_ticket("PB00000000", "password", SERVICE)
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::ValidateCodeError);
+    assert_eq!(err.get_ref().unwrap().to_string(), "solver offline");
+    assert!(mock.logins().is_empty());
+}
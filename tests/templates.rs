@@ -0,0 +1,76 @@
+#![cfg(feature = "validate-code")]
+
+mod common;
+
+use common::synth::{self, Rng, Style};
+use ustc_cas::captcha::{CaptchaErrorKind, Recognizer, Templates};
+
+#[test]
+fn bundled_templates_round_trip() {
+    let bundled = Templates::bundled();
+    assert_eq!(bundled.len(), 10);
+    let data = bundled.to_bytes().unwrap();
+    assert_eq!(data, include_bytes!("../src/captcha/templates.bin"));
+    assert_eq!(Templates::from_bytes(&data).unwrap(), bundled);
+}
+
+#[test]
+fn bundled_templates_know_only_the_captured_pixels() {
+    let data = Templates::bundled().to_bytes().unwrap();
+    for entry in data[8..].chunks(1 + 4 * 21) {
+        let known: Vec<u16> = entry[1 + 2 * 21..]
+            .chunks(2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]))
+            .collect();
+        // the first 256 pixels: 17 whole rows, and the first pixel of the next
+        assert!(known[..17].iter().all(|&row| row == 0x7fff));
+        assert_eq!(known[17..], [1, 0, 0, 0]);
+    }
+}
+
+#[test]
+fn templates_loaded_at_runtime_are_used() {
+    // relabel the templates of 7 as 'x'
+    let mut data = Templates::bundled().to_bytes().unwrap();
+    let entry = 1 + 4 * 21;
+    assert_eq!(data[8 + 7 * entry], b'7');
+    data[8 + 7 * entry] = b'x';
+
+    let path = std::env::temp_dir().join(format!("ustc-cas-templates-{}.bin", std::process::id()));
+    std::fs::write(&path, &data).unwrap();
+    let templates = Templates::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let image = synth::jpeg("1776", &Style::default(), &mut Rng::new(31));
+    let recognizer = Recognizer::new().templates(templates);
+    assert_eq!(recognizer.recognize(&image).unwrap().code(), "1xx6");
+}
+
+#[test]
+fn malformed_templates_are_an_error() {
+    let data = Templates::bundled().to_bytes().unwrap();
+
+    let mut bad_magic = data.clone();
+    bad_magic[0] = b'X';
+    let mut bad_size = data.clone();
+    bad_size[5] = 16;
+    let mut bad_row = data.clone();
+    bad_row[10] = 0x80;
+    // a template knowing no pixel, which would match anything
+    let mut unknown = data.clone();
+    unknown[8 + 1 + 2 * 21..8 + 1 + 4 * 21].fill(0);
+    for data in [
+        &bad_magic[..],
+        &data[..100],
+        &bad_size,
+        &bad_row,
+        &unknown,
+        &[],
+    ] {
+        let err = Templates::from_bytes(data).unwrap_err();
+        assert_eq!(err.kind(), CaptchaErrorKind::Templates);
+    }
+
+    let err = Templates::load("/nonexistent/templates.bin").unwrap_err();
+    assert_eq!(err.kind(), CaptchaErrorKind::Templates);
+}
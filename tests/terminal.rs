@@ -0,0 +1,77 @@
+#![cfg(feature = "validate-code")]
+
+use image::{codecs::jpeg::JpegEncoder, GrayImage, Luma};
+use std::io::{Cursor, Write};
+use std::sync::{Arc, Mutex};
+use ustc_cas::{ArtStyle, CaptchaSolver, TerminalSolver};
+
+/// a dark square in the middle of a light image.
+fn square() -> Vec<u8> {
+    let img = GrayImage::from_fn(8, 8, |x, y| {
+        if (2..6).contains(&x) && (2..6).contains(&y) {
+            Luma([0])
+        } else {
+            Luma([255])
+        }
+    });
+    let mut jpeg = vec![];
+    JpegEncoder::new_with_quality(&mut jpeg, 100)
+        .encode_image(&img)
+        .unwrap();
+    jpeg
+}
+
+#[derive(Clone, Default)]
+struct Shared(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Shared {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn half_block_art() {
+    let solver = TerminalSolver::with_io(Cursor::new(vec![]), std::io::sink());
+    let art = solver.render(&square()).unwrap();
+    assert_eq!(art, "\n  ████\n  ████\n\n");
+}
+
+#[test]
+fn ascii_art() {
+    let solver =
+        TerminalSolver::with_io(Cursor::new(vec![]), std::io::sink()).style(ArtStyle::Ascii);
+    let art = solver.render(&square()).unwrap();
+    assert_eq!(art, "\n  @@@@\n  @@@@\n\n");
+}
+
+#[test]
+fn wide_images_are_shrunk() {
+    let solver =
+        |columns| TerminalSolver::with_io(Cursor::new(vec![]), std::io::sink()).max_width(columns);
+    let art = solver(4).render(&square()).unwrap();
+    assert_eq!(art, " ▄▄\n ▀▀\n");
+    let art = solver(u32::MAX).render(&square()).unwrap();
+    assert_eq!(art, "\n  ████\n  ████\n\n");
+}
+
+#[test]
+fn answer_is_read_after_prompt() {
+    let output = Shared::default();
+    let solver = TerminalSolver::with_io(Cursor::new(b" 1234 \n".to_vec()), output.clone());
+    assert_eq!(solver.solve(&square()).unwrap(), "1234");
+
+    let shown = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+    assert!(shown.contains('█'));
+    assert!(shown.ends_with("validate code: "));
+}
+
+#[test]
+fn empty_answer_is_an_error() {
+    let solver = TerminalSolver::with_io(Cursor::new(b"\n".to_vec()), std::io::sink());
+    assert!(solver.solve(&square()).is_err());
+}
@@ -0,0 +1,182 @@
+#![cfg(feature = "validate-code")]
+
+mod common;
+
+use common::synth::{self, Rng, Style};
+use ustc_cas::captcha::{CaptchaErrorKind, LumaImage, Recognizer, TemplateTrainer, Templates};
+
+fn sample(style: &Style, rng: &mut Rng) -> (String, Vec<u8>) {
+    let code = synth::code(4, rng);
+    let image = synth::jpeg(&code, style, rng);
+    (code, image)
+}
+
+fn accuracy(recognizer: &Recognizer, style: &Style, rng: &mut Rng) -> f64 {
+    let total = 50;
+    let correct = (0..total)
+        .filter(|_| {
+            let (code, image) = sample(style, rng);
+            matches!(recognizer.recognize(&image), Ok(r) if r.code() == code)
+        })
+        .count();
+    correct as f64 / total as f64
+}
+
+#[test]
+fn trained_templates_recognize_codes() {
+    let mut rng = Rng::new(41);
+    let mut trainer = TemplateTrainer::new();
+    for _ in 0..40 {
+        let (code, image) = sample(&Style::default(), &mut rng);
+        trainer
+            .add(&LumaImage::decode(&image).unwrap(), &code)
+            .unwrap();
+    }
+    assert_eq!(trainer.counts().len(), 10);
+    assert_eq!(trainer.counts().values().sum::<usize>(), 160);
+
+    let templates = trainer.build().unwrap();
+    assert_eq!(templates.len(), 10);
+    let recognizer = Recognizer::new().templates(templates);
+    assert!(accuracy(&recognizer, &Style::default(), &mut rng) >= 0.98);
+}
+
+#[test]
+fn glyphs_drawn_two_ways_are_clustered() {
+    let mut rng = Rng::new(42);
+    let bold = Style {
+        bold: true,
+        ..Style::default()
+    };
+    let mut trainer = TemplateTrainer::new().clusters(2);
+    for i in 0..60 {
+        let style = if i % 2 == 0 { &bold } else { &Style::default() };
+        let (code, image) = sample(style, &mut rng);
+        trainer
+            .add(&LumaImage::decode(&image).unwrap(), &code)
+            .unwrap();
+    }
+
+    let templates = trainer.build().unwrap();
+    assert_eq!(templates.len(), 20);
+    let recognizer = Recognizer::new().templates(templates);
+    assert!(accuracy(&recognizer, &bold, &mut rng) >= 0.98);
+    assert!(accuracy(&recognizer, &Style::default(), &mut rng) >= 0.98);
+}
+
+#[test]
+fn mislabeled_sample_is_rejected() {
+    let mut rng = Rng::new(43);
+    let image = LumaImage::decode(&synth::jpeg("1234", &Style::default(), &mut rng)).unwrap();
+    let mut trainer = TemplateTrainer::new();
+
+    let err = trainer.add(&image, "123").unwrap_err();
+    assert_eq!(err.kind(), CaptchaErrorKind::Segmentation);
+    let err = trainer.add(&image, "12 4").unwrap_err();
+    assert_eq!(err.kind(), CaptchaErrorKind::Templates);
+    assert!(trainer.counts().is_empty());
+    let err = trainer.build().unwrap_err();
+    assert_eq!(err.kind(), CaptchaErrorKind::Templates);
+}
+
+#[test]
+fn too_many_templates_are_an_error() {
+    let mut rng = Rng::new(46);
+    // noise strong enough to flip pixels, so every glyph is a little different
+    let mut noisy = Style {
+        noise: 100,
+        ..Style::default()
+    };
+    let mut trainer = TemplateTrainer::new().clusters(255);
+    for i in 0..200 {
+        noisy.bold = i % 2 == 0;
+        let (code, image) = sample(&noisy, &mut rng);
+        // some samples are too noisy to segment
+        let _ = trainer.add(&LumaImage::decode(&image).unwrap(), &code);
+    }
+    let err = trainer.build().map(|t| t.len()).unwrap_err();
+    assert_eq!(err.kind(), CaptchaErrorKind::Templates);
+    assert!(err.to_string().contains("255"), "{err}");
+
+    let templates = trainer.clusters(20).build().unwrap();
+    assert!(templates.len() <= 200);
+    assert!(Templates::from_bytes(&templates.to_bytes().unwrap()).is_ok());
+}
+
+#[cfg(feature = "train")]
+#[test]
+fn training_binary_writes_templates() {
+    let dir = std::env::temp_dir().join(format!("ustc-cas-train-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut rng = Rng::new(44);
+    for i in 0..30 {
+        let (code, image) = sample(&Style::default(), &mut rng);
+        std::fs::write(dir.join(format!("{code}_{i}.jpg")), image).unwrap();
+    }
+    std::fs::write(dir.join("broken.jpg"), b"not an image").unwrap();
+    let output = dir.join("templates.bin");
+
+    let result = std::process::Command::new(env!("CARGO_BIN_EXE_ustc-cas-captcha-train"))
+        .arg(dir.join(""))
+        .arg(&output)
+        .output()
+        .unwrap();
+    assert!(result.status.success(), "{result:?}");
+    let stdout = String::from_utf8_lossy(&result.stdout);
+    assert!(stdout.contains("30 of 31 samples used"), "{stdout}");
+
+    let templates = Templates::load(&output).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    let recognizer = Recognizer::new().templates(templates);
+    assert!(accuracy(&recognizer, &Style::default(), &mut rng) >= 0.98);
+}
+
+/// Join template sets, all of them in the file format.
+fn join(sets: &[Vec<u8>]) -> Templates {
+    let mut data = sets[0][..8].to_vec();
+    data[7] = sets.iter().map(|s| s[7]).sum();
+    for set in sets {
+        data.extend(&set[8..]);
+    }
+    Templates::from_bytes(&data).unwrap()
+}
+
+#[test]
+fn nearest_templates_outvote_an_odd_one() {
+    let mut rng = Rng::new(45);
+    let bold = Style {
+        bold: true,
+        ..Style::default()
+    };
+    let image = synth::jpeg("1111", &bold, &mut rng);
+
+    // a mislabeled template: a bold 1 claiming to be 7
+    let mut odd = TemplateTrainer::new();
+    odd.add(&LumaImage::decode(&image).unwrap(), "1111")
+        .unwrap();
+    let mut odd = odd.build().unwrap().to_bytes().unwrap();
+    odd[8] = b'7';
+    let mut plain = TemplateTrainer::new();
+    for _ in 0..10 {
+        let (code, image) = sample(&Style::default(), &mut rng);
+        plain
+            .add(&LumaImage::decode(&image).unwrap(), &code)
+            .unwrap();
+    }
+    let templates = join(&[
+        Templates::bundled().to_bytes().unwrap(),
+        plain.build().unwrap().to_bytes().unwrap(),
+        odd,
+    ]);
+    assert_eq!(templates.len(), 21);
+
+    let nearest = Recognizer::new().templates(templates.clone()).neighbors(1);
+    assert_eq!(nearest.recognize(&image).unwrap().code(), "7777");
+
+    let recognition = Recognizer::new()
+        .templates(templates)
+        .recognize(&image)
+        .unwrap();
+    assert_eq!(recognition.code(), "1111");
+    assert_eq!(recognition.confidence(), 0.0);
+}
@@ -0,0 +1,168 @@
+mod common;
+
+use common::{MockCas, Response, SERVICE};
+use ustc_cas::{CasClient, CasProtocol, ErrorKind};
+
+fn client(mock: &MockCas, protocol: CasProtocol) -> CasClient {
+    CasClient::builder()
+        .base_url(mock.base_url())
+        .cas_protocol(protocol)
+        .build()
+        .unwrap()
+}
+
+/// Respond to every validation with `body`.
+fn respond(mock: &MockCas, body: &'static str) {
+    mock.route(move |r| {
+        r.path
+            .ends_with("serviceValidate")
+            .then(|| Response::ok(body))
+    });
+}
+
+#[tokio::test]
+async fn ticket_is_validated_with_attributes() {
+    let mock = MockCas::start();
+    let client = client(&mock, CasProtocol::V3);
+
+    let ticket = client
+        .get_ticket("PB00000001", "password", SERVICE)
+        .await
+        .unwrap();
+    let principal = client.validate_ticket(&ticket, SERVICE).await.unwrap();
+    assert_eq!(principal.user(), "PB00000001");
+    assert_eq!(principal.attribute("gid"), Some("2PB00000001"));
+    assert_eq!(principal.attribute("name"), Some("张三"));
+    assert_eq!(
+        principal.attribute_values("email"),
+        ["PB00000001@mail.ustc.edu.cn", "PB00000001@ustc.edu.cn"]
+    );
+    assert_eq!(principal.attributes().len(), 3);
+    assert_eq!(principal.attribute("missing"), None);
+
+    let request = mock.requests().pop().unwrap();
+    assert_eq!(request.path, "/p3/serviceValidate");
+    assert_eq!(request.query["service"], SERVICE);
+    assert_eq!(request.query["ticket"], ticket);
+}
+
+#[tokio::test]
+async fn cas2_validates_at_service_validate() {
+    let mock = MockCas::start();
+    let principal = client(&mock, CasProtocol::V2)
+        .validate_ticket("ST-PB00000002", SERVICE)
+        .await
+        .unwrap();
+    assert_eq!(principal.user(), "PB00000002");
+    assert!(principal.attributes().is_empty());
+    assert_eq!(mock.requests()[0].path, "/serviceValidate");
+}
+
+#[tokio::test]
+async fn rejected_tickets_are_errors() {
+    let mock = MockCas::start();
+    let client = client(&mock, CasProtocol::default());
+
+    let err = client.validate_ticket("bogus", SERVICE).await.unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::TicketInvalid);
+    assert_eq!(err.message(), Some("INVALID_TICKET: bogus"));
+    assert!(!err.is_retryable());
+
+    let err = client
+        .validate_ticket("ST-PB00000003", "https://example.com/?a=1&b=2")
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::ServiceUrlIncorrect);
+    assert_eq!(
+        err.message(),
+        Some("INVALID_SERVICE: https://example.com/?a=1&b=2")
+    );
+
+    respond(
+        &mock,
+        r#"<cas:serviceResponse xmlns:cas="http://www.yale.edu/tp/cas">
+    <cas:authenticationFailure code="INTERNAL_ERROR"/>
+    <cas:authenticationFailure code="INTERNAL_ERROR"></cas:authenticationFailure>
+</cas:serviceResponse>"#,
+    );
+    let err = client.validate_ticket("ST-1", SERVICE).await.unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::ServiceUnavailable);
+    assert_eq!(err.message(), Some("INTERNAL_ERROR"));
+}
+
+#[tokio::test]
+async fn responses_are_parsed_leniently() {
+    let mock = MockCas::start();
+    let client = client(&mock, CasProtocol::V3);
+
+    respond(
+        &mock,
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<serviceResponse>
+  <authenticationSuccess>
+    <user> PB00000004 </user>
+    <attributes>
+      <cas:isFromNewLogin>true</cas:isFromNewLogin>
+      <zjhm>&#x31;2&amp;3</zjhm>
+      <note><![CDATA[<b>bold</b> &amp;]]></note>
+      <empty/>
+      <nested><inner>x</inner></nested>
+    </attributes>
+  </authenticationSuccess>
+</serviceResponse>"#,
+    );
+    let principal = client.validate_ticket("ST-4", SERVICE).await.unwrap();
+    assert_eq!(principal.user(), "PB00000004");
+    assert_eq!(principal.attribute("isFromNewLogin"), Some("true"));
+    assert_eq!(principal.attribute("zjhm"), Some("12&3"));
+    assert_eq!(principal.attribute("note"), Some("<b>bold</b> &amp;"));
+    assert_eq!(principal.attribute("empty"), Some(""));
+    assert_eq!(principal.attribute("nested"), None);
+}
+
+#[tokio::test]
+async fn unexpected_responses_are_errors() {
+    for (body, kind) in [
+        ("<html>not xml</html>", ErrorKind::InvalidResponse),
+        (
+            "<cas:serviceResponse><cas:authenticationSuccess></cas:authenticationSuccess></cas:serviceResponse>",
+            ErrorKind::InvalidResponse,
+        ),
+        ("<html>系统维护中</html>", ErrorKind::ServiceUnavailable),
+    ] {
+        let mock = MockCas::start();
+        respond(&mock, body);
+        let err = client(&mock, CasProtocol::V3)
+            .validate_ticket("ST-5", SERVICE)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), kind, "{body}");
+    }
+
+    let mock = MockCas::start();
+    mock.route(|_| Some(Response::status(500)));
+    let err = client(&mock, CasProtocol::V3)
+        .validate_ticket("ST-5", SERVICE)
+        .await
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::HttpStatusError);
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn blocking_validation() {
+    let mock = MockCas::start();
+    let client = ustc_cas::blocking::CasClient::builder()
+        .base_url(mock.base_url())
+        .build()
+        .unwrap();
+    let ticket = client
+        .get_ticket("PB00000006", "password", SERVICE)
+        .unwrap();
+    let principal = client.validate_ticket(&ticket, SERVICE).unwrap();
+    assert_eq!(principal.user(), "PB00000006");
+    assert_eq!(principal.attribute_values("email").len(), 2);
+
+    let err = client.validate_ticket("bogus", SERVICE).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::TicketInvalid);
+}